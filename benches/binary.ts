@@ -1,4 +1,10 @@
-import { decodeNode, encodeNode, type BinaryNode } from "../dist/index.js";
+import {
+  decodeNode,
+  decodeNodes,
+  encodeNode,
+  encodeNodes,
+  type BinaryNode,
+} from "../dist/index.js";
 import { run, bench, do_not_optimize, boxplot, summary } from "mitata";
 import {
   encodeBinaryNode as encodeBinaryNodeOld,
@@ -40,6 +46,10 @@ const compressedEncoded = Buffer.concat([
   deflateSync(legacyEncoded.subarray(1)),
 ]);
 
+const BATCH_SIZE = 200;
+const batchNodes = Array.from({ length: BATCH_SIZE }, () => testNode);
+const batchEncoded = Array.from({ length: BATCH_SIZE }, () => legacyEncoded);
+
 const touchHotPath = (node: BinaryNode) => {
   const attrs = node.attrs;
   do_not_optimize(attrs.id);
@@ -122,6 +132,26 @@ boxplot(() => {
       touchHotPath(handle);
     }).gc("inner");
   });
+
+  summary(() => {
+    bench(`encodeNodes x${BATCH_SIZE} Rust WASM`, () => {
+      do_not_optimize(encodeNodes(batchNodes));
+    }).gc("inner");
+
+    bench(`encodeNode x${BATCH_SIZE} Rust WASM`, () => {
+      for (const node of batchNodes) do_not_optimize(encodeNode(node));
+    }).gc("inner");
+  });
+
+  summary(() => {
+    bench(`decodeNodes x${BATCH_SIZE} Rust WASM`, () => {
+      for (const node of decodeNodes(batchEncoded)) touchHotPath(node);
+    }).gc("inner");
+
+    bench(`decodeNode x${BATCH_SIZE} Rust WASM`, () => {
+      for (const buffer of batchEncoded) touchHotPath(decodeNode(buffer));
+    }).gc("inner");
+  });
 });
 
 await run();
@@ -0,0 +1,125 @@
+#!/usr/bin/env node
+// Generates pkg/proto-types.d.ts from Baileys' WAProto typings. Only the
+// `I*` message interfaces and enums are kept: `Long` fields become
+// `number | bigint` (decoding always returns `bigint`) and enums become unions
+// of their values, matching what the proto decode functions return.
+//
+// Needs Baileys installed, so it runs as `bun run gen:proto` rather than as
+// part of `build`.
+import { mkdirSync, readFileSync, writeFileSync } from "node:fs";
+import { dirname, resolve } from "node:path";
+import { fileURLToPath } from "node:url";
+import ts from "typescript";
+
+const __dirname = dirname(fileURLToPath(import.meta.url));
+const root = resolve(__dirname, "..");
+const source = resolve(root, "node_modules/baileys/WAProto/index.d.ts");
+const outFile = resolve(root, "pkg/proto-types.d.ts");
+
+const sourceFile = ts.createSourceFile(
+  source,
+  readFileSync(source, "utf8"),
+  ts.ScriptTarget.Latest,
+  true,
+);
+const { factory } = ts;
+const printer = ts.createPrinter();
+
+const numberType = () => factory.createKeywordTypeNode(ts.SyntaxKind.NumberKeyword);
+const bigintType = () => factory.createKeywordTypeNode(ts.SyntaxKind.BigIntKeyword);
+const longTypes = () => [numberType(), bigintType()];
+
+function isLong(node) {
+  return (
+    ts.isTypeReferenceNode(node) &&
+    /(^|\.)Long$/.test(node.typeName.getText(sourceFile))
+  );
+}
+
+function isNull(node) {
+  return (
+    ts.isLiteralTypeNode(node) && node.literal.kind === ts.SyntaxKind.NullKeyword
+  );
+}
+
+// `(number|Long|null)` -> `number | bigint`, `(Uint8Array|null)` -> `Uint8Array`.
+const cleanTypes = (context) => {
+  const visit = (node) => {
+    if (isLong(node)) return factory.createUnionTypeNode(longTypes());
+    if (ts.isUnionTypeNode(node)) {
+      const seen = new Set();
+      const types = node.types
+        .filter((type) => !isNull(type))
+        .flatMap((type) => (isLong(type) ? longTypes() : [ts.visitNode(type, visit)]))
+        .filter((type) => {
+          const text = printer.printNode(ts.EmitHint.Unspecified, type, sourceFile);
+          if (seen.has(text)) return false;
+          seen.add(text);
+          return true;
+        });
+      return types.length === 1 ? types[0] : factory.createUnionTypeNode(types);
+    }
+    if (ts.isParenthesizedTypeNode(node)) {
+      const inner = ts.visitNode(node.type, visit);
+      return ts.isUnionTypeNode(inner)
+        ? factory.updateParenthesizedType(node, inner)
+        : inner;
+    }
+    return ts.visitEachChild(node, visit, context);
+  };
+  return (node) => ts.visitNode(node, visit);
+};
+
+const [cleaned] = ts.transform(sourceFile, [cleanTypes]).transformed;
+
+function enumType(decl) {
+  const values = decl.members.map((member) =>
+    member.initializer && ts.isNumericLiteral(member.initializer)
+      ? member.initializer.text
+      : null,
+  );
+  return values.length > 0 && values.every((value) => value !== null)
+    ? [...new Set(values)].join(" | ")
+    : "number";
+}
+
+function emit(statements, indent) {
+  const lines = [];
+  for (const statement of statements) {
+    if (
+      ts.isModuleDeclaration(statement) &&
+      statement.body &&
+      ts.isModuleBlock(statement.body)
+    ) {
+      const body = emit(statement.body.statements, indent + "  ");
+      if (body.length === 0) continue;
+      lines.push(`${indent}namespace ${statement.name.text} {`, ...body, `${indent}}`);
+    } else if (ts.isInterfaceDeclaration(statement)) {
+      const text = printer.printNode(ts.EmitHint.Unspecified, statement, cleaned);
+      lines.push(...text.split("\n").map((line) => indent + line));
+    } else if (ts.isEnumDeclaration(statement)) {
+      lines.push(`${indent}type ${statement.name.text} = ${enumType(statement)};`);
+    }
+  }
+  return lines;
+}
+
+const protoNamespace = cleaned.statements.find(
+  (statement) => ts.isModuleDeclaration(statement) && statement.name.text === "proto",
+);
+if (!protoNamespace?.body || !ts.isModuleBlock(protoNamespace.body)) {
+  console.error(`No \`proto\` namespace in ${source}`);
+  process.exit(1);
+}
+
+const output = [
+  "/* Generated by scripts/gen-proto-types.mjs from Baileys' WAProto typings. */",
+  "export namespace proto {",
+  ...emit(protoNamespace.body.statements, "  "),
+  "}",
+  "",
+].join("\n");
+
+mkdirSync(dirname(outFile), { recursive: true });
+writeFileSync(outFile, output);
+console.log(`  → ${outFile}`);
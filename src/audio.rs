@@ -1,4 +1,4 @@
-use js_sys::{ArrayBuffer, Reflect, Uint8Array};
+use js_sys::Uint8Array;
 use std::io::Cursor;
 use symphonia::core::audio::{AudioBuffer, AudioBufferRef, SampleBuffer, Signal};
 use symphonia::core::codecs::{CODEC_TYPE_NULL, Decoder, DecoderOptions};
@@ -8,9 +8,9 @@ use symphonia::core::io::MediaSourceStream;
 use symphonia::core::meta::MetadataOptions;
 use symphonia::core::probe::Hint;
 use symphonia::core::units::TimeBase;
-use wasm_bindgen::{JsCast, prelude::*};
-use wasm_bindgen_futures::JsFuture;
-use web_sys::{ReadableStream, ReadableStreamDefaultReader};
+use wasm_bindgen::prelude::*;
+
+use crate::js_input::for_each_input_chunk;
 
 /// WhatsApp uses 64 buckets for visual waveforms.
 const WAVEFORM_SAMPLES: usize = 64;
@@ -392,62 +392,13 @@ impl DurationAccumulator {
 }
 
 async fn normalize_audio_input(input: JsValue) -> Result<Vec<u8>, JsValue> {
-    // Handle Uint8Array and ArrayBuffer (both can be wrapped with Uint8Array)
-    if input.is_instance_of::<Uint8Array>() || input.is_instance_of::<ArrayBuffer>() {
-        return Ok(copy_uint8_array(&Uint8Array::new(&input)));
-    }
-
-    if input.is_instance_of::<ReadableStream>() {
-        return read_stream(input.unchecked_ref::<ReadableStream>()).await;
-    }
-
-    Err(JsValue::from_str(
-        "Unsupported input type. Expected Uint8Array, ArrayBuffer, or ReadableStream",
-    ))
-}
-
-#[inline]
-fn copy_uint8_array(array: &Uint8Array) -> Vec<u8> {
-    let len = array.length() as usize;
-    let mut buffer = vec![0; len];
-    array.copy_to(&mut buffer);
-    buffer
-}
-
-async fn read_stream(stream: &ReadableStream) -> Result<Vec<u8>, JsValue> {
-    let reader = stream.get_reader();
-    let reader = reader.unchecked_into::<ReadableStreamDefaultReader>();
-    read_from_reader(reader).await
-}
-
-async fn read_from_reader(reader: ReadableStreamDefaultReader) -> Result<Vec<u8>, JsValue> {
-    // Pre-allocate with reasonable initial capacity
-    let mut chunks: Vec<u8> = Vec::with_capacity(64 * 1024);
-
-    loop {
-        let promise = reader.read();
-        let result = JsFuture::from(promise).await?;
-
-        let done = Reflect::get(&result, &JsValue::from_str("done"))?
-            .as_bool()
-            .unwrap_or(false);
-        if done {
-            break;
-        }
-
-        let value = Reflect::get(&result, &JsValue::from_str("value"))?;
-        if !value.is_undefined() && !value.is_null() {
-            let chunk = Uint8Array::new(&value);
-            let chunk_len = chunk.length() as usize;
-            let prev_len = chunks.len();
-            chunks.reserve(chunk_len);
-            chunks.resize(prev_len + chunk_len, 0);
-            chunk.copy_to(&mut chunks[prev_len..]);
-        }
-    }
-
-    reader.release_lock();
-    Ok(chunks)
+    let mut data = Vec::new();
+    for_each_input_chunk(input, |chunk| {
+        data.extend_from_slice(chunk);
+        Ok(())
+    })
+    .await?;
+    Ok(data)
 }
 
 struct DecoderContext {
@@ -1,18 +1,34 @@
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
 use js_sys::{Array, Object, Uint8Array};
+use serde::{Deserialize, Serialize};
 use std::borrow::Cow;
-use std::cell::UnsafeCell;
+use std::cell::{Cell, UnsafeCell};
+use std::fmt;
+use std::io::Write;
 use std::mem;
+use std::ops::{Deref, Range};
 use std::rc::Rc;
+use tsify_next::Tsify;
 use wacore_binary::{
+    consts::FLAG_COMPRESSED,
     marshal::{marshal_ref, unmarshal_ref},
     node::{AttrsRef, NodeContentRef, NodeRef, NodeStr, ValueRef},
-    util::unpack,
 };
+use wasm_bindgen::convert::RefFromWasmAbi;
 use wasm_bindgen::prelude::*;
 
+use crate::decode_limits::{
+    DecodeLimits, check_limits, check_payload_len, inflate_into, unpack_limited,
+};
+use crate::exported::{exported_ref, prototype_of};
+use crate::jid::Jid;
+use crate::node_builder::BinaryNodeBuilder;
+use crate::node_json::portable_bytes;
+
 #[wasm_bindgen]
 extern "C" {
-    #[wasm_bindgen(typescript_type = "EncodingNode")]
+    #[wasm_bindgen(typescript_type = "EncodingNode | BinaryNodeBuilder")]
     pub type EncodingNode;
 
     #[wasm_bindgen(extends = Object, typescript_type = "{ [key: string]: string }")]
@@ -23,83 +39,408 @@ extern "C" {
     #[derive(Clone, Debug, PartialEq, Eq)]
     pub type Content;
 
-    #[wasm_bindgen(structural, method, getter)]
-    pub fn tag(this: &EncodingNode) -> String;
+    #[wasm_bindgen(typescript_type = "BinaryNode")]
+    pub type BinaryNodeObject;
 
-    #[wasm_bindgen(structural, method, getter)]
-    pub fn attrs(this: &EncodingNode) -> Attrs;
+    #[wasm_bindgen(typescript_type = "InternalBinaryNode | undefined")]
+    pub type OptionalInternalNode;
+
+    #[wasm_bindgen(extends = Array, typescript_type = "(EncodingNode | BinaryNodeBuilder)[]")]
+    pub type EncodingNodeList;
+
+    #[wasm_bindgen(extends = Array, typescript_type = "Uint8Array[]")]
+    pub type BufferList;
+
+    #[wasm_bindgen(extends = Array, typescript_type = "InternalBinaryNode[]")]
+    pub type InternalBinaryNodeList;
+
+    #[wasm_bindgen(structural, method, getter, js_name = tag)]
+    fn tag_value(this: &EncodingNode) -> JsValue;
+
+    #[wasm_bindgen(structural, method, getter, js_name = attrs)]
+    fn attrs_value(this: &EncodingNode) -> JsValue;
 
     #[wasm_bindgen(structural, method, getter)]
     pub fn content(this: &EncodingNode) -> JsValue;
 }
 
+/// Encodes a JS node, re-sending the original bytes of unmodified decoded nodes.
+pub(crate) fn marshal_js_node(
+    node: &EncodingNode,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, JsValue> {
+    let original = InternalBinaryNode::from_js(node).and_then(|node| node.original_bytes());
+    let frame = match original {
+        Some(bytes) => bytes,
+        None => {
+            let node_ref = js_to_node_ref_with(node, options)?;
+            marshal_ref(&node_ref).map_err(|e| JsValue::from_str(&e.to_string()))?
+        }
+    };
+    compress_frame(frame, options)
+}
+
+/// Formats `Jid` attribute values so the encoder emits them as JID tokens.
 #[inline]
-pub(crate) fn js_to_node_ref(val: &EncodingNode) -> Result<NodeRef<'static>, JsValue> {
-    let attrs_obj = val.attrs().unchecked_into::<Object>();
-    let entries = Object::entries(&attrs_obj);
-    let len = entries.length();
-    let mut attrs = Vec::with_capacity(len as usize);
-
-    for i in 0..len {
-        let entry = entries.get(i);
-        let entry_arr = entry.unchecked_into::<Array>();
-        let key_js = entry_arr.get(0);
-        let value_js = entry_arr.get(1);
-
-        let key = match key_js.as_string() {
-            Some(k) => k,
-            None => continue,
-        };
+fn jid_attr_string(value: &JsValue) -> Option<String> {
+    if !value.is_object() {
+        return None;
+    }
+    Jid::string_from_js(value)
+}
+
+/// Options accepted by `encodeNode` and `NoiseSession.encodeFrame`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct EncodeOptions {
+    /// Reject the node, listing every attribute or content value that would
+    /// otherwise be silently dropped, instead of encoding what is left.
+    #[tsify(optional)]
+    pub strict: Option<bool>,
+    /// Deflate the payload and set the compressed flag. `"auto"` only does so
+    /// for payloads of at least `threshold` bytes, and only if it shrinks them.
+    #[tsify(optional, type = "boolean | \"auto\"")]
+    pub compress: Option<CompressMode>,
+    /// Minimum payload size for `compress: "auto"`, default 1024 bytes.
+    #[tsify(optional)]
+    pub threshold: Option<u32>,
+}
 
-        let value_str = if let Some(s) = value_js.as_string() {
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum CompressMode {
+    Always(bool),
+    Auto(AutoCompress),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AutoCompress {
+    Auto,
+}
+
+const DEFAULT_COMPRESS_THRESHOLD: u32 = 1024;
+
+/// Applies `options.compress` to a marshalled frame (`flag || payload`).
+fn compress_frame(frame: Vec<u8>, options: &EncodeOptions) -> Result<Vec<u8>, JsValue> {
+    let auto = match options.compress {
+        None | Some(CompressMode::Always(false)) => return Ok(frame),
+        Some(CompressMode::Always(true)) => false,
+        Some(CompressMode::Auto(_)) => true,
+    };
+    let Some((&flag, payload)) = frame.split_first() else {
+        return Ok(frame);
+    };
+    if flag & FLAG_COMPRESSED != 0 {
+        return Ok(frame);
+    }
+    let threshold = options.threshold.unwrap_or(DEFAULT_COMPRESS_THRESHOLD) as usize;
+    if auto && payload.len() < threshold {
+        return Ok(frame);
+    }
+
+    let mut encoder = ZlibEncoder::new(vec![flag | FLAG_COMPRESSED], Compression::default());
+    encoder
+        .write_all(payload)
+        .map_err(|e| JsValue::from_str(&format!("Failed to deflate frame: {e}")))?;
+    let compressed = encoder
+        .finish()
+        .map_err(|e| JsValue::from_str(&format!("Failed to deflate frame: {e}")))?;
+
+    if auto && compressed.len() >= frame.len() {
+        return Ok(frame);
+    }
+    Ok(compressed)
+}
+
+/// Location of a node inside the tree being encoded, rendered lazily as
+/// e.g. `iq.content[0]` only when something has to be reported.
+#[derive(Clone, Copy)]
+enum NodePath<'a> {
+    Root(&'a str),
+    Child(&'a NodePath<'a>, u32),
+}
+
+impl fmt::Display for NodePath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodePath::Root(tag) => f.write_str(tag),
+            NodePath::Child(parent, index) => write!(f, "{parent}.content[{index}]"),
+        }
+    }
+}
+
+fn js_type_name(value: &JsValue) -> String {
+    if value.is_null() {
+        "null".to_string()
+    } else if Array::is_array(value) {
+        "array".to_string()
+    } else {
+        value.js_typeof().as_string().unwrap_or_default()
+    }
+}
+
+#[derive(Default)]
+struct NodeEncoder {
+    strict: bool,
+    /// Also read byte content tagged as in `PortableBinaryNode`.
+    portable: bool,
+    issues: Vec<String>,
+    /// Buffers of decoded nodes spliced into the result.
+    buffers: Vec<Rc<[u8]>>,
+}
+
+impl NodeEncoder {
+    fn report(&mut self, path: impl fmt::Display, reason: &str) {
+        if self.strict {
+            self.issues.push(format!("{path}: {reason}"));
+        }
+    }
+
+    fn attr_value(&mut self, path: &NodePath<'_>, key: &str, value: &JsValue) -> Option<String> {
+        if let Some(s) = value.as_string() {
             if s.is_empty() || s.chars().all(|c| c.is_whitespace()) {
-                continue;
+                self.report(format_args!("{path}.attrs.{key}"), "empty string");
+                return None;
             }
-            s
-        } else if let Some(n) = value_js.as_f64() {
-            n.to_string()
-        } else if let Some(b) = value_js.as_bool() {
-            b.to_string()
+            Some(s)
+        } else if let Some(n) = value.as_f64() {
+            Some(n.to_string())
+        } else if let Some(b) = value.as_bool() {
+            Some(b.to_string())
+        } else if let Some(jid) = jid_attr_string(value) {
+            Some(jid)
         } else {
-            continue;
+            let reason = format!("unsupported value ({})", js_type_name(value));
+            self.report(format_args!("{path}.attrs.{key}"), &reason);
+            None
+        }
+    }
+
+    fn node(
+        &mut self,
+        val: &JsValue,
+        parent: Option<&NodePath<'_>>,
+    ) -> Result<NodeRef<'static>, JsValue> {
+        if !val.is_object() || Array::is_array(val) {
+            let path = parent.map_or_else(|| "node".to_string(), ToString::to_string);
+            return Err(JsValue::from_str(&format!(
+                "{path}: expected a node object, got {}",
+                js_type_name(val)
+            )));
+        }
+        if let Some(decoded) = InternalBinaryNode::from_js(val) {
+            return self.decoded(&decoded, parent);
+        }
+        if let Some(builder) = BinaryNodeBuilder::from_js(val) {
+            self.buffers.extend(builder.buffers().iter().cloned());
+            return Ok(builder.to_node_ref());
+        }
+        let node = val.unchecked_ref::<EncodingNode>();
+
+        let tag = node.tag_value().as_string().unwrap_or_default();
+        if tag.is_empty() {
+            let path = parent.map_or_else(|| "node".to_string(), ToString::to_string);
+            return Err(JsValue::from_str(&format!(
+                "{path}.tag: expected a non-empty string"
+            )));
+        }
+        let path = parent.copied().unwrap_or(NodePath::Root(&tag));
+
+        let attrs = self.attrs(&path, &node.attrs_value());
+        let content = self.content(&path, &node.content())?;
+
+        Ok(NodeRef::new(NodeStr::Owned(tag.into()), attrs, content))
+    }
+
+    /// Splices a decoded node in without copying, rebuilding only the parts
+    /// materialised for JS once its tree has been modified.
+    fn decoded(
+        &mut self,
+        node: &InternalBinaryNode,
+        parent: Option<&NodePath<'_>>,
+    ) -> Result<NodeRef<'static>, JsValue> {
+        self.buffers.push(Rc::clone(&node._owned_data));
+        if !node.needs_rebuild() {
+            return Ok(node.node_ref().clone());
+        }
+
+        let node_ref = node.node_ref();
+        let path = parent.copied().unwrap_or(NodePath::Root(&node_ref.tag));
+        let attrs = match node.cache.attrs() {
+            Some(attrs) => self.attrs(&path, &attrs),
+            None => node_ref.attrs.clone(),
+        };
+        let content = match node.cache.content() {
+            Some(content) => self.content(&path, &content)?,
+            None => self.decoded_content(node, &path)?,
         };
+        Ok(NodeRef::new(node_ref.tag.clone(), attrs, content))
+    }
 
-        attrs.push((
-            NodeStr::Owned(key.into()),
-            ValueRef::String(NodeStr::Owned(value_str.into())),
-        ));
-    }
-
-    let content_js = val.content();
-
-    let content = if content_js.is_undefined() {
-        Ok(None)
-    } else if let Some(string_value) = content_js.as_string() {
-        Ok(Some(NodeContentRef::String(NodeStr::Owned(
-            string_value.into(),
-        ))))
-    } else if content_js.is_instance_of::<Uint8Array>() {
-        let byte_array: Uint8Array = content_js.unchecked_into();
-        Ok(Some(NodeContentRef::Bytes(Cow::Owned(byte_array.to_vec()))))
-    } else if Array::is_array(&content_js) {
-        let arr = Array::from(&content_js);
-        let nodes = (0..arr.length())
-            .map(|i| {
-                let child_val = arr.get(i);
-                let child_node = child_val.unchecked_into::<EncodingNode>();
-                js_to_node_ref(&child_node)
+    /// The decoded content, with the children handed out by `getChild` and
+    /// friends read back in case they were edited.
+    fn decoded_content(
+        &mut self,
+        node: &InternalBinaryNode,
+        path: &NodePath<'_>,
+    ) -> Result<Option<NodeContentRef<'static>>, JsValue> {
+        let handed_out = node.cache.children();
+        if handed_out.is_empty() {
+            return Ok(node.node_ref().content.as_deref().cloned());
+        }
+        let nodes = decoded_children(node.node_ref())
+            .iter()
+            .zip(handed_out)
+            .enumerate()
+            .map(|(i, (child, handed_out))| match handed_out {
+                Some(js) => self.node(&js, Some(&NodePath::Child(path, i as u32))),
+                None => Ok(child.clone()),
             })
             .collect::<Result<Vec<NodeRef<'static>>, _>>()?;
         Ok(Some(NodeContentRef::Nodes(nodes.into_boxed_slice())))
-    } else {
-        Err(JsValue::from_str("Invalid content type"))
+    }
+
+    fn attrs(&mut self, path: &NodePath<'_>, attrs_js: &JsValue) -> AttrsRef<'static> {
+        let mut attrs = Vec::new();
+        if attrs_js.is_object() {
+            let entries = Object::entries(attrs_js.unchecked_ref::<Object>());
+            attrs.reserve(entries.length() as usize);
+
+            for entry in entries.iter() {
+                let entry_arr = entry.unchecked_into::<Array>();
+                let Some(key) = entry_arr.get(0).as_string() else {
+                    continue;
+                };
+                let Some(value) = self.attr_value(path, &key, &entry_arr.get(1)) else {
+                    continue;
+                };
+                attrs.push((
+                    NodeStr::Owned(key.into()),
+                    ValueRef::String(NodeStr::Owned(value.into())),
+                ));
+            }
+        } else if !attrs_js.is_undefined() {
+            let reason = format!("expected an object, got {}", js_type_name(attrs_js));
+            self.report(format_args!("{path}.attrs"), &reason);
+        }
+        AttrsRef::from_vec(attrs)
+    }
+
+    fn content(
+        &mut self,
+        path: &NodePath<'_>,
+        content_js: &JsValue,
+    ) -> Result<Option<NodeContentRef<'static>>, JsValue> {
+        let content = if content_js.is_undefined() {
+            None
+        } else if let Some(string_value) = content_js.as_string() {
+            Some(NodeContentRef::String(NodeStr::Owned(string_value.into())))
+        } else if let Some(byte_array) = content_js.dyn_ref::<Uint8Array>() {
+            Some(NodeContentRef::Bytes(Cow::Owned(byte_array.to_vec())))
+        } else if let Some(arr) = content_js.dyn_ref::<Array>() {
+            let nodes = (0..arr.length())
+                .map(|i| self.node(&arr.get(i), Some(&NodePath::Child(path, i))))
+                .collect::<Result<Vec<NodeRef<'static>>, _>>()?;
+            Some(NodeContentRef::Nodes(nodes.into_boxed_slice()))
+        } else if self.portable
+            && let Some(bytes) = portable_bytes(content_js)
+        {
+            match bytes {
+                Ok(bytes) => Some(NodeContentRef::Bytes(Cow::Owned(bytes))),
+                Err(reason) if self.strict => {
+                    self.report(format_args!("{path}.content.base64"), reason);
+                    None
+                }
+                Err(reason) => {
+                    return Err(JsValue::from_str(&format!(
+                        "{path}.content.base64: {reason}"
+                    )));
+                }
+            }
+        } else if self.strict {
+            let reason = format!("unsupported content ({})", js_type_name(content_js));
+            self.report(format_args!("{path}.content"), &reason);
+            None
+        } else {
+            return Err(JsValue::from_str(&format!(
+                "Invalid content type at {path}.content"
+            )));
+        };
+        Ok(content)
+    }
+
+    fn finish<'a>(self, node: Cow<'a, NodeRef<'static>>) -> Result<JsNodeRef<'a>, JsValue> {
+        if !self.issues.is_empty() {
+            return Err(JsValue::from_str(&format!(
+                "Invalid node:\n  {}",
+                self.issues.join("\n  ")
+            )));
+        }
+        Ok(JsNodeRef {
+            node,
+            buffers: self.buffers,
+        })
+    }
+}
+
+/// A node tree read from JS. Decoded nodes inside it are spliced in without
+/// copying, so it keeps their buffers alive.
+pub(crate) struct JsNodeRef<'a> {
+    node: Cow<'a, NodeRef<'static>>,
+    buffers: Vec<Rc<[u8]>>,
+}
+
+impl JsNodeRef<'static> {
+    /// A node parsed from `buffers`.
+    pub(crate) fn owned(node: NodeRef<'static>, buffers: Vec<Rc<[u8]>>) -> Self {
+        JsNodeRef {
+            node: Cow::Owned(node),
+            buffers,
+        }
+    }
+
+    /// The node and the buffers it borrows from, to be kept together.
+    pub(crate) fn into_parts(self) -> (NodeRef<'static>, Vec<Rc<[u8]>>) {
+        (self.node.into_owned(), self.buffers)
+    }
+}
+
+impl Deref for JsNodeRef<'_> {
+    type Target = NodeRef<'static>;
+
+    fn deref(&self) -> &NodeRef<'static> {
+        &self.node
+    }
+}
+
+#[inline]
+pub(crate) fn js_to_node_ref(val: &EncodingNode) -> Result<JsNodeRef<'static>, JsValue> {
+    js_to_node_ref_with(val, &EncodeOptions::default())
+}
+
+pub(crate) fn js_to_node_ref_with(
+    val: &EncodingNode,
+    options: &EncodeOptions,
+) -> Result<JsNodeRef<'static>, JsValue> {
+    let mut encoder = NodeEncoder {
+        strict: options.strict.unwrap_or(false),
+        ..NodeEncoder::default()
     };
+    let node = encoder.node(val, None)?;
+    encoder.finish(Cow::Owned(node))
+}
 
-    Ok(NodeRef::new(
-        NodeStr::Owned(val.tag().into()),
-        AttrsRef::from_vec(attrs),
-        content?,
-    ))
+/// Reads a `PortableBinaryNode` in strict mode.
+pub(crate) fn portable_to_node_ref(val: &JsValue) -> Result<JsNodeRef<'static>, JsValue> {
+    let mut encoder = NodeEncoder {
+        strict: true,
+        portable: true,
+        ..NodeEncoder::default()
+    };
+    let node = encoder.node(val, None)?;
+    encoder.finish(Cow::Owned(node))
 }
 
 #[wasm_bindgen(typescript_custom_section)]
@@ -109,22 +450,318 @@ export interface BinaryNode {
     attrs: { [key: string]: string };
     content?: BinaryNode[] | string | Uint8Array;
 }
+
+export interface EncodingNode {
+    tag: string;
+    attrs: { [key: string]: string | number | boolean | Jid | undefined };
+    content?: (EncodingNode | BinaryNodeBuilder)[] | string | Uint8Array;
+}
 "#;
 
 #[wasm_bindgen]
 pub struct InternalBinaryNode {
     _owned_data: Rc<[u8]>,
     node_ref: NodeRef<'static>,
-    cached_attrs: UnsafeCell<Option<Attrs>>,
-    cached_content: UnsafeCell<Option<Content>>,
+    /// Where this node's encoded bytes live in `_owned_data`; only known for
+    /// top-level nodes, whose unmodified bytes can be re-sent as-is.
+    root: Option<Range<usize>>,
+    /// Shared by every wrapper of one decoded tree and set once any of them
+    /// has `attrs`/`content` replaced. In-place edits of the objects handed
+    /// out are found by comparing them with the decoded tree (`edited`).
+    modified: Rc<Cell<bool>>,
+    /// Shared with the views `select` returns for this node itself.
+    cache: Rc<NodeCache>,
+}
+
+thread_local! {
+    static INTERNAL_NODE_PROTOTYPE: Object = {
+        let empty = NodeRef::new(NodeStr::Owned("".into()), AttrsRef::from_vec(Vec::new()), None);
+        prototype_of(InternalBinaryNode::from_parts(Rc::from(Vec::new()), empty, None).into())
+    };
+}
+
+/// `attrs` and `content` once materialised for JS (and possibly modified).
+#[derive(Default)]
+struct NodeCache {
+    attrs: UnsafeCell<Option<Attrs>>,
+    content: UnsafeCell<Option<Content>>,
+    /// Wrappers of the decoded children handed out so far, by index, so a
+    /// child looked up twice or later found in `content` is the same object.
+    children: UnsafeCell<Vec<Option<JsValue>>>,
+}
+
+impl NodeCache {
+    #[inline]
+    fn attrs(&self) -> Option<Attrs> {
+        // SAFETY: WASM is single-threaded
+        unsafe { (*self.attrs.get()).clone() }
+    }
+
+    #[inline]
+    fn content(&self) -> Option<Content> {
+        // SAFETY: WASM is single-threaded
+        unsafe { (*self.content.get()).clone() }
+    }
+
+    #[inline]
+    fn children(&self) -> Vec<Option<JsValue>> {
+        // SAFETY: WASM is single-threaded
+        unsafe { (*self.children.get()).clone() }
+    }
+
+    /// The wrapper of child `index` out of `len`, created by `wrap` on first use.
+    fn child(&self, index: usize, len: usize, wrap: impl FnOnce() -> JsValue) -> JsValue {
+        // SAFETY: WASM is single-threaded, and `wrap` does not touch this cache
+        let children = unsafe { &mut *self.children.get() };
+        if children.is_empty() {
+            children.resize(len, None);
+        }
+        children[index].get_or_insert_with(wrap).clone()
+    }
+
+    #[inline]
+    fn is_empty(&self) -> bool {
+        // SAFETY: WASM is single-threaded
+        unsafe {
+            (*self.attrs.get()).is_none()
+                && (*self.content.get()).is_none()
+                && (*self.children.get()).is_empty()
+        }
+    }
+
+    #[inline]
+    fn set_attrs(&self, attrs: Attrs) {
+        // SAFETY: WASM is single-threaded
+        unsafe { *self.attrs.get() = Some(attrs) };
+    }
+
+    #[inline]
+    fn set_content(&self, content: Content) {
+        // SAFETY: WASM is single-threaded
+        unsafe { *self.content.get() = Some(content) };
+    }
+}
+
+#[inline]
+fn decoded_children<'a>(node: &'a NodeRef<'static>) -> &'a [NodeRef<'static>] {
+    match node.content.as_deref() {
+        Some(NodeContentRef::Nodes(nodes)) => nodes,
+        _ => &[],
+    }
+}
+
+fn child_edited(child: &JsValue) -> bool {
+    InternalBinaryNode::from_js(child).is_some_and(|child| child.edited())
+}
+
+fn attrs_match(attrs: &Attrs, decoded: &AttrsRef<'_>) -> bool {
+    let decoded = decoded.as_slice();
+    Object::keys(attrs).length() as usize == decoded.len()
+        && decoded.iter().all(|(key, value)| {
+            js_sys::Reflect::get(attrs, &JsValue::from_str(key))
+                .ok()
+                .and_then(|v| v.as_string())
+                .is_some_and(|v| v == value.as_str())
+        })
+}
+
+#[inline]
+fn js_tag(node: &JsValue) -> Option<String> {
+    js_sys::Reflect::get(node, &JsValue::from_str("tag"))
+        .ok()
+        .and_then(|tag| tag.as_string())
+}
+
+#[inline]
+fn js_children(node: &JsValue) -> Option<Array> {
+    js_sys::Reflect::get(node, &JsValue::from_str("content"))
+        .ok()
+        .and_then(|content| content.dyn_into::<Array>().ok())
+}
+
+/// Pushes the descendants of `node` matching `segments` onto `out`.
+fn select_from(node: &JsValue, segments: &[&str], out: &Array) {
+    if let Some(decoded) = InternalBinaryNode::from_js(node) {
+        decoded.select_into(segments, out);
+    } else if let Some(children) = js_children(node) {
+        select_js(&children, segments, out);
+    }
+}
+
+fn select_js(children: &Array, segments: &[&str], out: &Array) {
+    let Some((first, rest)) = segments.split_first() else {
+        return;
+    };
+    for child in children.iter() {
+        if js_tag(&child).as_deref() != Some(*first) {
+            continue;
+        }
+        if rest.is_empty() {
+            out.push(&child);
+        } else {
+            select_from(&child, rest, out);
+        }
+    }
 }
 
 impl InternalBinaryNode {
+    /// A top-level node parsed from `data`; `root` is where its bytes are.
+    pub(crate) fn from_parts(
+        data: Rc<[u8]>,
+        node_ref: NodeRef<'static>,
+        root: Option<Range<usize>>,
+    ) -> InternalBinaryNode {
+        InternalBinaryNode {
+            _owned_data: data,
+            node_ref,
+            root,
+            modified: Rc::default(),
+            cache: Rc::default(),
+        }
+    }
+
+    /// Borrows the node behind `value` if it is an `InternalBinaryNode`.
+    pub(crate) fn from_js(value: &JsValue) -> Option<<Self as RefFromWasmAbi>::Anchor> {
+        INTERNAL_NODE_PROTOTYPE.with(|prototype| exported_ref::<Self>(value, prototype))
+    }
+
     #[inline(always)]
     fn node_ref(&self) -> &NodeRef<'static> {
         &self.node_ref
     }
 
+    #[inline]
+    fn wrap(&self, node_ref: &NodeRef<'static>) -> InternalBinaryNode {
+        InternalBinaryNode {
+            _owned_data: Rc::clone(&self._owned_data),
+            node_ref: node_ref.clone(),
+            root: None,
+            modified: Rc::clone(&self.modified),
+            cache: Rc::default(),
+        }
+    }
+
+    /// Whether `node_ref` may be stale. Only nodes whose `attrs`/`content`
+    /// were materialised can hold edits, for them or their descendants.
+    #[inline]
+    fn needs_rebuild(&self) -> bool {
+        !self.cache.is_empty() && (self.modified.get() || self.edited())
+    }
+
+    /// Whether the `attrs`/`content` handed out for this node, or for the
+    /// children handed out by lookups, no longer match the decoded tree.
+    fn edited(&self) -> bool {
+        if let Some(attrs) = self.cache.attrs()
+            && !attrs_match(&attrs, &self.node_ref().attrs)
+        {
+            return true;
+        }
+        match self.cache.content() {
+            Some(content) => !self.content_matches(&content),
+            None => self.cache.children().iter().flatten().any(child_edited),
+        }
+    }
+
+    fn content_matches(&self, content: &Content) -> bool {
+        match self.node_ref().content.as_deref() {
+            None => false,
+            Some(NodeContentRef::String(s)) => content.as_string().as_deref() == Some(&**s),
+            Some(NodeContentRef::Bytes(bytes)) => content
+                .dyn_ref::<Uint8Array>()
+                .is_some_and(|arr| arr.length() as usize == bytes.len() && arr.to_vec() == **bytes),
+            Some(NodeContentRef::Nodes(nodes)) => {
+                let Some(arr) = content.dyn_ref::<Array>() else {
+                    return false;
+                };
+                let children = self.cache.children();
+                arr.length() as usize == nodes.len()
+                    && children.len() == nodes.len()
+                    && children.iter().enumerate().all(|(i, child)| {
+                        child.as_ref().is_some_and(|child| {
+                            Object::is(child, &arr.get(i as u32)) && !child_edited(child)
+                        })
+                    })
+            }
+        }
+    }
+
+    /// The frame this node was decoded from, if nothing in its tree changed.
+    pub(crate) fn original_bytes(&self) -> Option<Vec<u8>> {
+        if self.modified.get() || self.edited() {
+            return None;
+        }
+        let root = self.root.clone()?;
+        let mut bytes = Vec::with_capacity(root.len() + 1);
+        bytes.push(0);
+        bytes.extend_from_slice(&self._owned_data[root]);
+        Some(bytes)
+    }
+
+    /// The node as currently seen from JS: the decoded tree, or a rebuilt one
+    /// if it was modified.
+    pub(crate) fn current_node_ref(&self) -> Result<JsNodeRef<'_>, JsValue> {
+        if !self.needs_rebuild() {
+            return NodeEncoder::default().finish(Cow::Borrowed(self.node_ref()));
+        }
+        let mut encoder = NodeEncoder::default();
+        let node = encoder.decoded(self, None)?;
+        encoder.finish(Cow::Owned(node))
+    }
+
+    /// Encodes the node, reusing the original bytes when nothing was modified.
+    pub(crate) fn marshal(&self) -> Result<Vec<u8>, JsValue> {
+        if let Some(bytes) = self.original_bytes() {
+            return Ok(bytes);
+        }
+        marshal_ref(&self.current_node_ref()?).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+
+    /// The wrapper of decoded child `index`, shared by `content` and every
+    /// lookup, so edits made through it are encoded with this node.
+    fn child(&self, index: usize) -> JsValue {
+        let children = decoded_children(self.node_ref());
+        self.cache
+            .child(index, children.len(), || self.wrap(&children[index]).into())
+    }
+
+    /// Calls `visit` for each child tagged `tag` until it returns `false`.
+    /// Decoded children are matched on the parsed tree and only the matches
+    /// are wrapped; once `content` was replaced from JS, its elements are
+    /// visited instead.
+    fn for_each_child(&self, tag: &str, mut visit: impl FnMut(JsValue) -> bool) {
+        if let Some(content) = self.cache.content() {
+            let Some(children) = content.dyn_ref::<Array>() else {
+                return;
+            };
+            for child in children.iter() {
+                if js_tag(&child).as_deref() == Some(tag) && !visit(child) {
+                    return;
+                }
+            }
+            return;
+        }
+        for (i, child) in decoded_children(self.node_ref()).iter().enumerate() {
+            if &*child.tag == tag && !visit(self.child(i)) {
+                return;
+            }
+        }
+    }
+
+    /// Pushes the descendants matching `segments` onto `out`.
+    fn select_into(&self, segments: &[&str], out: &Array) {
+        let Some((first, rest)) = segments.split_first() else {
+            return;
+        };
+        self.for_each_child(first, |child| {
+            if rest.is_empty() {
+                out.push(&child);
+            } else {
+                select_from(&child, rest, out);
+            }
+            true
+        });
+    }
+
     #[inline]
     fn convert_attrs(attrs: &AttrsRef<'_>) -> Attrs {
         let obj = Object::new();
@@ -157,7 +794,7 @@ impl InternalBinaryNode {
         if let Some(content) = self.content() {
             let content_js: JsValue = content.into();
             let content_value = if Array::is_array(&content_js) {
-                self.serialize_child_nodes(&content_js)
+                self.serialize_child_nodes(content_js.unchecked_ref())
             } else {
                 content_js
             };
@@ -167,8 +804,7 @@ impl InternalBinaryNode {
         obj.into()
     }
 
-    fn serialize_child_nodes(&self, content_js: &JsValue) -> JsValue {
-        let arr = Array::from(content_js);
+    fn serialize_child_nodes(&self, arr: &Array) -> JsValue {
         let json_arr = Array::new_with_length(arr.length());
         let to_json_key = JsValue::from_str("toJSON");
 
@@ -185,92 +821,337 @@ impl InternalBinaryNode {
         json_arr.into()
     }
 
+    /// The returned object may be edited in place; edits are picked up when
+    /// the node is encoded, and reading alone keeps the original bytes.
     #[wasm_bindgen(getter)]
     pub fn attrs(&self) -> Attrs {
-        // SAFETY: WASM is single-threaded
-        let cached = unsafe { &mut *self.cached_attrs.get() };
-        if let Some(attrs) = cached.as_ref() {
-            return attrs.clone();
+        if let Some(attrs) = self.cache.attrs() {
+            return attrs;
         }
 
         let attrs = Self::convert_attrs(&self.node_ref().attrs);
-        *cached = Some(attrs.clone());
+        self.cache.set_attrs(attrs.clone());
         attrs
     }
 
     #[wasm_bindgen(setter)]
     pub fn set_attrs(&self, new_attrs: Attrs) {
-        // SAFETY: WASM is single-threaded
-        unsafe { *self.cached_attrs.get() = Some(new_attrs) };
+        self.modified.set(true);
+        self.cache.set_attrs(new_attrs);
     }
 
+    /// Like `attrs`, the content may be edited in place.
     #[wasm_bindgen(getter)]
     pub fn content(&self) -> Option<Content> {
-        // SAFETY: WASM is single-threaded
-        let cached = unsafe { &mut *self.cached_content.get() };
-        if let Some(content) = cached.as_ref() {
-            return Some(content.clone());
+        if let Some(content) = self.cache.content() {
+            return Some(content);
         }
 
-        let result: Option<Content> = match self.node_ref().content.as_deref() {
-            Some(NodeContentRef::Bytes(bytes)) => {
-                Some(Uint8Array::from(bytes.as_ref()).unchecked_into())
-            }
-            Some(NodeContentRef::String(s)) => Some(JsValue::from_str(s).unchecked_into()),
-            Some(NodeContentRef::Nodes(nodes)) => {
+        let result: Content = match self.node_ref().content.as_deref()? {
+            NodeContentRef::Bytes(bytes) => Uint8Array::from(bytes.as_ref()).unchecked_into(),
+            NodeContentRef::String(s) => JsValue::from_str(s).unchecked_into(),
+            NodeContentRef::Nodes(nodes) => {
                 let arr = Array::new_with_length(nodes.len() as u32);
-                for (i, node_ref) in nodes.iter().enumerate() {
-                    let child = InternalBinaryNode {
-                        _owned_data: Rc::clone(&self._owned_data),
-                        node_ref: node_ref.clone(),
-                        cached_attrs: UnsafeCell::new(None),
-                        cached_content: UnsafeCell::new(None),
-                    };
-                    arr.set(i as u32, child.into());
+                for i in 0..nodes.len() {
+                    arr.set(i as u32, self.child(i));
                 }
-                Some(arr.unchecked_into())
+                arr.unchecked_into()
             }
-            None => None,
         };
 
-        *cached = result.clone();
-        result
+        self.cache.set_content(result.clone());
+        Some(result)
     }
 
     #[wasm_bindgen(setter)]
     pub fn set_content(&self, new_content: Content) {
-        // SAFETY: WASM is single-threaded
-        unsafe { *self.cached_content.get() = Some(new_content) };
+        self.modified.set(true);
+        self.cache.set_content(new_content);
+    }
+
+    /// Reads one attribute without materialising the `attrs` object.
+    #[wasm_bindgen(js_name = getAttr)]
+    pub fn get_attr(&self, key: &str) -> Option<String> {
+        if let Some(attrs) = self.cache.attrs() {
+            return js_sys::Reflect::get(&attrs, &JsValue::from_str(key))
+                .ok()
+                .and_then(|v| v.as_string());
+        }
+
+        self.node_ref()
+            .attrs
+            .as_slice()
+            .iter()
+            .find(|(k, _)| &**k == key)
+            .map(|(_, v)| v.as_str().to_string())
+    }
+
+    /// First direct child tagged `tag`. The result is the same object found
+    /// in `content`, so changes made through it are encoded with this node.
+    /// If `content` was replaced from JS, its elements are returned as given.
+    #[wasm_bindgen(js_name = getChild)]
+    pub fn get_child(&self, tag: &str) -> OptionalInternalNode {
+        let mut found = JsValue::UNDEFINED;
+        self.for_each_child(tag, |child| {
+            found = child;
+            false
+        });
+        found.unchecked_into()
+    }
+
+    /// Direct children tagged `tag`, shared with `content` like `getChild`.
+    #[wasm_bindgen(js_name = getChildren)]
+    pub fn get_children(&self, tag: &str) -> InternalBinaryNodeList {
+        let out = Array::new();
+        self.for_each_child(tag, |child| {
+            out.push(&child);
+            true
+        });
+        out.unchecked_into()
+    }
+
+    #[wasm_bindgen(js_name = hasChild)]
+    pub fn has_child(&self, tag: &str) -> bool {
+        if self.cache.content().is_none() {
+            return decoded_children(self.node_ref())
+                .iter()
+                .any(|child| &*child.tag == tag);
+        }
+        let mut found = false;
+        self.for_each_child(tag, |_| {
+            found = true;
+            false
+        });
+        found
+    }
+
+    /// Collects every node matching a `>`-separated tag path anchored at this
+    /// node, e.g. `node.select("iq>usync>list>user")` on an `iq` stanza.
+    /// Matches are shared with `content` like `getChild`.
+    pub fn select(&self, path: &str) -> InternalBinaryNodeList {
+        let out = Array::new();
+        let segments: Vec<&str> = path.split('>').map(str::trim).collect();
+
+        let Some((root, rest)) = segments.split_first() else {
+            return out.unchecked_into();
+        };
+        if &*self.node_ref().tag != *root {
+            return out.unchecked_into();
+        }
+
+        if rest.is_empty() {
+            let view = InternalBinaryNode {
+                root: self.root.clone(),
+                cache: Rc::clone(&self.cache),
+                ..self.wrap(self.node_ref())
+            };
+            out.push(&view.into());
+        } else {
+            self.select_into(rest, &out);
+        }
+
+        out.unchecked_into()
     }
 }
 
 #[wasm_bindgen(js_name = encodeNode)]
-pub fn encode_node(node_val: EncodingNode) -> Result<Uint8Array, JsValue> {
-    let node_ref = js_to_node_ref(&node_val)?;
-    let bytes = marshal_ref(&node_ref).map_err(|e| JsValue::from_str(&e.to_string()))?;
+pub fn encode_node(
+    node_val: EncodingNode,
+    options: Option<EncodeOptions>,
+) -> Result<Uint8Array, JsValue> {
+    let bytes = marshal_js_node(&node_val, &options.unwrap_or_default())?;
     Ok(Uint8Array::from(bytes.as_slice()))
 }
 
-#[wasm_bindgen(js_name = decodeNode)]
-pub fn decode_node(data: Vec<u8>) -> Result<InternalBinaryNode, JsValue> {
+/// Extends the borrow of an `Rc`'s bytes to `'static`, so nodes parsed from
+/// them can be stored next to the `Rc` that owns them.
+///
+/// # Safety
+///
+/// Every node parsed from the returned slice must be dropped before the last
+/// clone of `data`, e.g. by keeping a clone alongside it.
+unsafe fn static_bytes(data: &Rc<[u8]>) -> &'static [u8] {
+    // SAFETY: `Rc<[u8]>` contents are immutable and never move, and the
+    // caller keeps them alive for as long as the returned slice is used.
+    unsafe { mem::transmute::<&[u8], &'static [u8]>(&data[..]) }
+}
+
+/// Unpacks and parses a frame, returning the node together with the buffer it borrows from.
+pub(crate) fn unmarshal_owned(
+    data: &[u8],
+    limits: &DecodeLimits,
+) -> Result<(Rc<[u8]>, NodeRef<'static>), JsValue> {
     if data.is_empty() {
         return Err(JsValue::from_str("Input data cannot be empty"));
     }
 
-    let unpacked_cow = unpack(&data).map_err(|e| JsValue::from_str(&e.to_string()))?;
+    let unpacked_cow = unpack_limited(data, limits)?;
 
     let owned_data: Rc<[u8]> = match unpacked_cow {
         Cow::Owned(vec) => Rc::from(vec.into_boxed_slice()),
         Cow::Borrowed(slice) => Rc::from(slice),
     };
 
-    let static_data: &'static [u8] = unsafe { mem::transmute(owned_data.as_ref()) };
+    // SAFETY: `owned_data` is returned with the node.
+    let static_data = unsafe { static_bytes(&owned_data) };
+    check_limits(static_data, limits)?;
     let node_ref = unmarshal_ref(static_data).map_err(|e| JsValue::from_str(&e.to_string()))?;
 
-    Ok(InternalBinaryNode {
-        _owned_data: owned_data,
+    Ok((owned_data, node_ref))
+}
+
+/// Builds a plain `BinaryNode` object (no wrappers) from a parsed node.
+pub(crate) fn node_ref_to_js(node: &NodeRef<'_>) -> JsValue {
+    node_ref_to_js_with(node, &|bytes| Uint8Array::from(bytes).into())
+}
+
+/// Like [`node_ref_to_js`], with byte content converted by `bytes`.
+pub(crate) fn node_ref_to_js_with(
+    node: &NodeRef<'_>,
+    bytes: &impl Fn(&[u8]) -> JsValue,
+) -> JsValue {
+    let obj = Object::new();
+    let _ = js_sys::Reflect::set(
+        &obj,
+        &JsValue::from_str("tag"),
+        &JsValue::from_str(&node.tag),
+    );
+    let _ = js_sys::Reflect::set(
+        &obj,
+        &JsValue::from_str("attrs"),
+        &InternalBinaryNode::convert_attrs(&node.attrs),
+    );
+
+    let content: Option<JsValue> = match node.content.as_deref() {
+        Some(NodeContentRef::Bytes(data)) => Some(bytes(data)),
+        Some(NodeContentRef::String(s)) => Some(JsValue::from_str(s)),
+        Some(NodeContentRef::Nodes(nodes)) => Some(
+            nodes
+                .iter()
+                .map(|node| node_ref_to_js_with(node, bytes))
+                .collect::<Array>()
+                .into(),
+        ),
+        None => None,
+    };
+    if let Some(content) = content {
+        let _ = js_sys::Reflect::set(&obj, &JsValue::from_str("content"), &content);
+    }
+
+    obj.into()
+}
+
+/// Decodes a frame. Pass `limits` when the input is untrusted; exceeding one
+/// throws a `DecodeLimitError`.
+#[wasm_bindgen(js_name = decodeNode)]
+pub fn decode_node(
+    data: Vec<u8>,
+    limits: Option<DecodeLimits>,
+) -> Result<InternalBinaryNode, JsValue> {
+    let (owned_data, node_ref) = unmarshal_owned(&data, &limits.unwrap_or_default())?;
+    let root = 0..owned_data.len();
+    Ok(InternalBinaryNode::from_parts(
+        owned_data,
         node_ref,
-        cached_attrs: UnsafeCell::new(None),
-        cached_content: UnsafeCell::new(None),
-    })
+        Some(root),
+    ))
+}
+
+/// Result of [`encode_nodes`]: node `i` is `buffer.subarray(offsets[i], offsets[i + 1])`.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct EncodedNodes {
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub buffer: Vec<u8>,
+    pub offsets: Vec<u32>,
+}
+
+/// Encodes many nodes in one call, packing the results back to back.
+#[wasm_bindgen(js_name = encodeNodes)]
+pub fn encode_nodes(
+    nodes: EncodingNodeList,
+    options: Option<EncodeOptions>,
+) -> Result<EncodedNodes, JsValue> {
+    let options = options.unwrap_or_default();
+    let mut buffer = Vec::new();
+    let mut offsets = Vec::with_capacity(nodes.length() as usize + 1);
+    offsets.push(0);
+
+    for (i, node) in nodes.iter().enumerate() {
+        let bytes = marshal_js_node(node.unchecked_ref(), &options)
+            .map_err(|e| prefix_error(e, &format!("nodes[{i}]")))?;
+        buffer.extend_from_slice(&bytes);
+        offsets.push(buffer.len() as u32);
+    }
+
+    Ok(EncodedNodes { buffer, offsets })
+}
+
+/// Decodes many frames at once. The payloads are unpacked straight into one
+/// shared arena (uncompressed ones are copied from JS memory only once), so
+/// all returned nodes keep a single allocation alive.
+#[wasm_bindgen(js_name = decodeNodes)]
+pub fn decode_nodes(
+    buffers: BufferList,
+    limits: Option<DecodeLimits>,
+) -> Result<InternalBinaryNodeList, JsValue> {
+    let limits = limits.unwrap_or_default();
+    let mut arena = Vec::new();
+    let mut compressed = Vec::new();
+    let mut ranges = Vec::with_capacity(buffers.length() as usize);
+
+    for (i, buffer) in buffers.iter().enumerate() {
+        let data = buffer
+            .dyn_into::<Uint8Array>()
+            .map_err(|_| JsValue::from_str(&format!("buffers[{i}]: expected a Uint8Array")))?;
+        let len = data.length();
+        if len == 0 {
+            return Err(JsValue::from_str(&format!(
+                "buffers[{i}]: Input data cannot be empty"
+            )));
+        }
+
+        let start = arena.len();
+        if data.get_index(0) & FLAG_COMPRESSED == 0 {
+            check_payload_len(len as usize - 1, &limits)
+                .map_err(|e| prefix_error(e, &format!("buffers[{i}]")))?;
+            arena.resize(start + len as usize - 1, 0);
+            data.subarray(1, len).copy_to(&mut arena[start..]);
+        } else {
+            compressed.resize(len as usize - 1, 0);
+            data.subarray(1, len).copy_to(&mut compressed);
+            inflate_into(&compressed, &limits, &mut arena)
+                .map_err(|e| prefix_error(e, &format!("buffers[{i}]")))?;
+        }
+        ranges.push(start..arena.len());
+    }
+
+    let owned_data: Rc<[u8]> = Rc::from(arena.into_boxed_slice());
+    // SAFETY: every node below keeps a clone of `owned_data`.
+    let static_data = unsafe { static_bytes(&owned_data) };
+
+    let out = Array::new_with_length(ranges.len() as u32);
+    for (i, range) in ranges.into_iter().enumerate() {
+        let data = &static_data[range.clone()];
+        check_limits(data, &limits).map_err(|e| prefix_error(e, &format!("buffers[{i}]")))?;
+        let node_ref =
+            unmarshal_ref(data).map_err(|e| JsValue::from_str(&format!("buffers[{i}]: {e}")))?;
+        let node = InternalBinaryNode::from_parts(Rc::clone(&owned_data), node_ref, Some(range));
+        out.set(i as u32, node.into());
+    }
+
+    Ok(out.unchecked_into())
+}
+
+/// Prefixes an error message, keeping typed errors like `DecodeLimitError`.
+fn prefix_error(err: JsValue, prefix: &str) -> JsValue {
+    if let Some(error) = err.dyn_ref::<js_sys::Error>() {
+        error.set_message(&format!("{prefix}: {}", String::from(error.message())));
+        return err;
+    }
+    match err.as_string() {
+        Some(message) => JsValue::from_str(&format!("{prefix}: {message}")),
+        None => err,
+    }
 }
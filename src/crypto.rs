@@ -26,16 +26,29 @@ pub struct HkdfInfo {
     pub info: Option<String>,
 }
 
+/// HKDF-SHA256 extract-and-expand shared by the JS `hkdf` export and the
+/// media key derivation.
+pub(crate) fn hkdf_expand(
+    ikm: &[u8],
+    salt: Option<&[u8]>,
+    info: &[u8],
+    expanded_length: usize,
+) -> Result<Vec<u8>, JsValue> {
+    let hk = Hkdf::<Sha256>::new(salt, ikm);
+    let mut okm = vec![0u8; expanded_length];
+
+    hk.expand(info, &mut okm)
+        .map_err(|_| JsValue::from_str("HKDF expansion failed"))?;
+
+    Ok(okm)
+}
+
 #[wasm_bindgen(js_name = hkdf)]
 pub fn hkdf(buffer: &[u8], expanded_length: usize, info: HkdfInfo) -> Result<Uint8Array, JsValue> {
     let salt_bytes = info.salt.as_deref();
     let info_bytes = info.info.as_deref().map(|s| s.as_bytes()).unwrap_or(&[]);
 
-    let hk = Hkdf::<Sha256>::new(salt_bytes, buffer);
-    let mut okm = vec![0u8; expanded_length];
-
-    hk.expand(info_bytes, &mut okm)
-        .map_err(|_| JsValue::from_str("HKDF expansion failed"))?;
+    let okm = hkdf_expand(buffer, salt_bytes, info_bytes, expanded_length)?;
 
     let arr = Uint8Array::new_with_length(okm.len() as u32);
     arr.copy_from(&okm);
@@ -0,0 +1,266 @@
+use flate2::read::ZlibDecoder;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::io::Read;
+use tsify_next::Tsify;
+use wacore_binary::consts::{
+    AD_JID, BINARY_8, BINARY_20, BINARY_32, DICTIONARY_0, DICTIONARY_3, FB_JID, FLAG_COMPRESSED,
+    HEX_8, INTEROP_JID, JID_PAIR, LIST_8, LIST_16, LIST_EMPTY, NIBBLE_8,
+};
+use wacore_binary::util::unpack;
+use wasm_bindgen::prelude::*;
+
+use crate::js_error::typed_js_error;
+
+/// Upper bounds applied when decoding untrusted frames. Unset fields are unlimited.
+///
+/// `maxInflatedBytes` is enforced while inflating, so an oversized payload is
+/// never materialised. The structural limits are checked on the token stream
+/// before it is parsed.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Tsify)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeLimits {
+    #[tsify(optional)]
+    pub max_inflated_bytes: Option<u32>,
+    #[tsify(optional)]
+    pub max_depth: Option<u32>,
+    #[tsify(optional)]
+    pub max_children: Option<u32>,
+    #[tsify(optional)]
+    pub max_attributes: Option<u32>,
+}
+
+impl DecodeLimits {
+    fn has_structural_limits(&self) -> bool {
+        self.max_depth.is_some() || self.max_children.is_some() || self.max_attributes.is_some()
+    }
+}
+
+/// A `DecodeLimitError`: `err.name === "DecodeLimitError"` and `err.limit`
+/// names the exceeded option.
+fn limit_error(limit: &str, message: String) -> JsValue {
+    typed_js_error("DecodeLimitError", "limit", limit, &message)
+}
+
+fn inflated_limit_error(len: usize, max: usize) -> JsValue {
+    limit_error(
+        "maxInflatedBytes",
+        format!("Frame of {len} bytes exceeds maxInflatedBytes ({max})"),
+    )
+}
+
+/// Checks the payload size of an uncompressed frame against `maxInflatedBytes`.
+pub(crate) fn check_payload_len(len: usize, limits: &DecodeLimits) -> Result<(), JsValue> {
+    match limits.max_inflated_bytes {
+        Some(max) if len > max as usize => Err(inflated_limit_error(len, max as usize)),
+        _ => Ok(()),
+    }
+}
+
+/// Inflates a compressed payload onto the end of `out`, stopping past
+/// `maxInflatedBytes`.
+pub(crate) fn inflate_into(
+    payload: &[u8],
+    limits: &DecodeLimits,
+    out: &mut Vec<u8>,
+) -> Result<(), JsValue> {
+    let start = out.len();
+    let mut decoder = ZlibDecoder::new(payload);
+    let read = match limits.max_inflated_bytes {
+        Some(max) => decoder.take(max as u64 + 1).read_to_end(out),
+        None => decoder.read_to_end(out),
+    };
+    read.map_err(|e| JsValue::from_str(&format!("Failed to inflate frame: {e}")))?;
+    if let Some(max) = limits.max_inflated_bytes
+        && out.len() - start > max as usize
+    {
+        out.truncate(start);
+        return Err(limit_error(
+            "maxInflatedBytes",
+            format!("Inflated frame exceeds maxInflatedBytes ({max})"),
+        ));
+    }
+    Ok(())
+}
+
+/// Like `wacore_binary::util::unpack`, but stops inflating past `maxInflatedBytes`.
+pub(crate) fn unpack_limited<'a>(
+    data: &'a [u8],
+    limits: &DecodeLimits,
+) -> Result<Cow<'a, [u8]>, JsValue> {
+    if limits.max_inflated_bytes.is_none() {
+        return unpack(data).map_err(|e| JsValue::from_str(&e.to_string()));
+    }
+    let (flag, payload) = data
+        .split_first()
+        .ok_or_else(|| JsValue::from_str("Input data cannot be empty"))?;
+
+    if flag & FLAG_COMPRESSED == 0 {
+        check_payload_len(payload.len(), limits)?;
+        return Ok(Cow::Borrowed(payload));
+    }
+
+    let mut inflated = Vec::new();
+    inflate_into(payload, limits, &mut inflated)?;
+    Ok(Cow::Owned(inflated))
+}
+
+/// What is left of a value while skipping it: nested values or fixed bytes.
+enum Skip {
+    Value,
+    Bytes(usize),
+}
+
+/// Walks the token stream without building nodes, so limits hold before
+/// the recursive parser sees the data. Token tags come from
+/// `wacore_binary::consts`, the ones its decoder reads.
+struct TokenScanner<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl TokenScanner<'_> {
+    fn truncated() -> JsValue {
+        JsValue::from_str("Unexpected end of node data")
+    }
+
+    fn byte(&mut self) -> Result<u8, JsValue> {
+        let byte = *self.data.get(self.pos).ok_or_else(Self::truncated)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn int(&mut self, len: usize) -> Result<usize, JsValue> {
+        let bytes = self.take(len)?;
+        Ok(bytes.iter().fold(0, |n, &b| (n << 8) | b as usize))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], JsValue> {
+        let end = self.pos.checked_add(len).ok_or_else(Self::truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or_else(Self::truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    /// Size of a list tag, or `None` if `tag` is not one.
+    fn list_size(&mut self, tag: u8) -> Result<Option<usize>, JsValue> {
+        Ok(match tag {
+            LIST_EMPTY => Some(0),
+            LIST_8 => Some(self.int(1)?),
+            LIST_16 => Some(self.int(2)?),
+            _ => None,
+        })
+    }
+
+    /// Skips a string, byte or JID value starting with `tag`.
+    fn skip_value(&mut self, tag: u8) -> Result<(), JsValue> {
+        let mut pending = Vec::new();
+        let mut tag = Some(tag);
+        loop {
+            let tag = match tag.take() {
+                Some(tag) => tag,
+                None => match pending.pop() {
+                    Some(Skip::Value) => self.byte()?,
+                    Some(Skip::Bytes(len)) => {
+                        self.take(len)?;
+                        continue;
+                    }
+                    None => return Ok(()),
+                },
+            };
+            match tag {
+                LIST_EMPTY => {}
+                1..DICTIONARY_0 => {}
+                DICTIONARY_0..=DICTIONARY_3 => pending.push(Skip::Bytes(1)),
+                BINARY_8 => pending.push(Skip::Bytes(self.int(1)?)),
+                BINARY_20 => pending.push(Skip::Bytes(self.int(3)? & 0xf_ffff)),
+                BINARY_32 => pending.push(Skip::Bytes(self.int(4)?)),
+                HEX_8 | NIBBLE_8 => pending.push(Skip::Bytes(self.int(1)? & 0x7f)),
+                JID_PAIR => pending.extend([Skip::Value, Skip::Value]),
+                AD_JID => pending.extend([Skip::Value, Skip::Bytes(2)]),
+                FB_JID => pending.extend([Skip::Value, Skip::Bytes(2), Skip::Value]),
+                INTEROP_JID => {
+                    pending.extend([Skip::Value, Skip::Bytes(4), Skip::Value]);
+                }
+                _ => {
+                    return Err(JsValue::from_str(&format!(
+                        "Invalid token {tag} at position {}",
+                        self.pos - 1
+                    )));
+                }
+            }
+        }
+    }
+}
+
+/// Checks depth, child and attribute counts of an unpacked frame before it is
+/// parsed, without recursing. The root is depth 1.
+pub(crate) fn check_limits(data: &[u8], limits: &DecodeLimits) -> Result<(), JsValue> {
+    if !limits.has_structural_limits() {
+        return Ok(());
+    }
+    let mut scanner = TokenScanner { data, pos: 0 };
+    // Nodes still to read at each open level.
+    let mut remaining = vec![1usize];
+    while let Some(siblings) = remaining.last_mut() {
+        if *siblings == 0 {
+            remaining.pop();
+            continue;
+        }
+        *siblings -= 1;
+        let depth = remaining.len() as u32;
+        if let Some(max) = limits.max_depth
+            && depth > max
+        {
+            return Err(limit_error(
+                "maxDepth",
+                format!("Nodes are nested {depth} levels deep, maxDepth is {max}"),
+            ));
+        }
+
+        let tag = scanner.byte()?;
+        let size = match scanner.list_size(tag)? {
+            Some(size) if size > 0 => size,
+            _ => return Err(JsValue::from_str("Invalid node: missing list size")),
+        };
+        let description = scanner.byte()?;
+        scanner.skip_value(description)?;
+
+        let count = (size - 1) >> 1;
+        if let Some(max) = limits.max_attributes
+            && count > max as usize
+        {
+            return Err(limit_error(
+                "maxAttributes",
+                format!("Node at depth {depth} has {count} attributes, maxAttributes is {max}"),
+            ));
+        }
+        for _ in 0..count * 2 {
+            let tag = scanner.byte()?;
+            scanner.skip_value(tag)?;
+        }
+        if size % 2 == 1 {
+            continue;
+        }
+
+        let tag = scanner.byte()?;
+        match scanner.list_size(tag)? {
+            Some(children) => {
+                if let Some(max) = limits.max_children
+                    && children > max as usize
+                {
+                    return Err(limit_error(
+                        "maxChildren",
+                        format!(
+                            "Node at depth {depth} has {children} children, maxChildren is {max}"
+                        ),
+                    ));
+                }
+                remaining.push(children);
+            }
+            None => scanner.skip_value(tag)?,
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use js_sys::{Object, Reflect};
+use wasm_bindgen::convert::RefFromWasmAbi;
+use wasm_bindgen::prelude::*;
+
+/// The prototype of an exported class, taken from a throwaway instance since
+/// the generated class cannot be imported back into Rust.
+pub(crate) fn prototype_of(instance: JsValue) -> Object {
+    Object::get_prototype_of(&instance)
+}
+
+/// Borrows the Rust value behind `value` if it is an instance of the exported
+/// class with `prototype` (`value instanceof Class`), without copying it.
+///
+/// Relies on the JS glue of wasm-bindgen 0.2 (checked against 0.2.100, the
+/// version in `Cargo.toml`): instances keep their pointer in `__wbg_ptr`, and
+/// `free()` zeroes it.
+pub(crate) fn exported_ref<T>(value: &JsValue, prototype: &Object) -> Option<T::Anchor>
+where
+    T: RefFromWasmAbi<Abi = u32>,
+{
+    if !prototype.is_prototype_of(value) {
+        return None;
+    }
+    let ptr = Reflect::get(value, &JsValue::from_str("__wbg_ptr"))
+        .ok()?
+        .as_f64()? as u32;
+    if ptr == 0 {
+        // Already freed from JS.
+        return None;
+    }
+    // SAFETY: this is the check the glue makes for a `&T` argument
+    // (`_assertClass`, an `instanceof`, then `__wbg_ptr`). `prototype` belongs
+    // to `T`'s generated class alone, so `value` was created by it or built on
+    // its prototype, and a live instance's `__wbg_ptr` points at a `T`. Forging
+    // the field on a bare `Object.create(prototype)` would break the generated
+    // methods just the same.
+    Some(unsafe { T::ref_from_abi(ptr) })
+}
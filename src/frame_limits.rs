@@ -0,0 +1,141 @@
+use serde::{Deserialize, Serialize};
+use tsify_next::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::js_error::typed_js_error;
+
+/// Frames carry a 3-byte big-endian length prefix.
+pub(crate) const FRAME_HEADER_LEN: usize = 3;
+pub(crate) const MAX_FRAME_LEN: usize = 0xff_ffff;
+
+/// Bounds on inbound frames for a `NoiseSession`. Unset fields are unlimited.
+///
+/// `maxFrameSize` is checked as soon as a length prefix arrives, and
+/// `maxBufferedBytes` before an incomplete frame is buffered, so neither is
+/// ever allocated.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Tsify)]
+#[tsify(into_wasm_abi, from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct FrameLimits {
+    #[tsify(optional)]
+    pub max_frame_size: Option<u32>,
+    #[tsify(optional)]
+    pub max_buffered_bytes: Option<u32>,
+}
+
+/// A `FrameLimitError`: `err.name === "FrameLimitError"` and `err.limit`
+/// names the exceeded option.
+pub(crate) fn frame_limit_error(limit: &str, message: String) -> JsValue {
+    typed_js_error("FrameLimitError", "limit", limit, &message)
+}
+
+/// Splits the inbound byte stream into frames, refusing input that would break
+/// its `FrameLimits` before any of it is buffered.
+#[derive(Debug, Default)]
+pub(crate) struct FrameDecoder {
+    buffer: Vec<u8>,
+    /// Start of the first frame not yet handed out.
+    start: usize,
+    limits: FrameLimits,
+}
+
+impl FrameDecoder {
+    pub(crate) fn new(limits: FrameLimits) -> Self {
+        Self {
+            limits,
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn buffered_len(&self) -> usize {
+        self.buffer.len() - self.start
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.buffer.clear();
+        self.start = 0;
+    }
+
+    /// Buffers `data`, unless a frame header in it (or completed by it) exceeds
+    /// `maxFrameSize`, or the incomplete frame left at the end would exceed
+    /// `maxBufferedBytes`. Nothing is buffered on error.
+    pub(crate) fn feed(&mut self, data: &[u8]) -> Result<(), JsValue> {
+        if self.limits.max_frame_size.is_some() || self.limits.max_buffered_bytes.is_some() {
+            self.check(data)?;
+        }
+        if self.start > 0 {
+            self.buffer.drain(..self.start);
+            self.start = 0;
+        }
+        self.buffer.extend_from_slice(data);
+        Ok(())
+    }
+
+    /// Walks the frame headers of the buffered bytes followed by `data`.
+    fn check(&self, data: &[u8]) -> Result<(), JsValue> {
+        let buffered = &self.buffer[self.start..];
+        let byte_at = |i: usize| match buffered.get(i) {
+            Some(&byte) => byte,
+            None => data[i - buffered.len()],
+        };
+        let total = buffered.len() + data.len();
+
+        let mut pos = 0;
+        while total - pos >= FRAME_HEADER_LEN {
+            let len = u32::from_be_bytes([0, byte_at(pos), byte_at(pos + 1), byte_at(pos + 2)]);
+            if let Some(max) = self.limits.max_frame_size.filter(|&max| len > max) {
+                return Err(frame_limit_error(
+                    "maxFrameSize",
+                    format!("Frame of {len} bytes exceeds maxFrameSize ({max})"),
+                ));
+            }
+            let frame_len = FRAME_HEADER_LEN + len as usize;
+            if total - pos < frame_len {
+                break;
+            }
+            pos += frame_len;
+        }
+
+        let partial = total - pos;
+        if let Some(max) = self
+            .limits
+            .max_buffered_bytes
+            .filter(|&max| partial > max as usize)
+        {
+            return Err(frame_limit_error(
+                "maxBufferedBytes",
+                format!(
+                    "Buffering {partial} bytes of an incomplete frame exceeds maxBufferedBytes ({max})"
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// The body of the next complete frame, if one is buffered.
+    pub(crate) fn decode_frame(&mut self) -> Option<Vec<u8>> {
+        let buffered = &self.buffer[self.start..];
+        if buffered.len() < FRAME_HEADER_LEN {
+            return None;
+        }
+        let len = u32::from_be_bytes([0, buffered[0], buffered[1], buffered[2]]) as usize;
+        let frame = buffered
+            .get(FRAME_HEADER_LEN..FRAME_HEADER_LEN + len)?
+            .to_vec();
+        self.start += FRAME_HEADER_LEN + len;
+        if self.start == self.buffer.len() {
+            self.clear();
+        }
+        Some(frame)
+    }
+}
+
+/// Refuses payloads the 3-byte length prefix cannot describe.
+pub(crate) fn check_frame_len(len: usize) -> Result<(), JsValue> {
+    if len > MAX_FRAME_LEN {
+        return Err(JsValue::from_str(&format!(
+            "Frame too large: {len} bytes exceeds the 24-bit frame length limit ({MAX_FRAME_LEN})"
+        )));
+    }
+    Ok(())
+}
@@ -0,0 +1,286 @@
+use js_sys::{Number, Object};
+use serde::Serialize;
+use std::fmt;
+use tsify_next::Tsify;
+use wacore_libsignal::core::{DeviceId, ProtocolAddress as CoreProtocolAddress};
+use wasm_bindgen::prelude::*;
+
+use crate::exported::{exported_ref, prototype_of};
+use crate::protocol_address::ProtocolAddress;
+
+pub const DEFAULT_USER_SERVER: &str = "s.whatsapp.net";
+pub const LID_SERVER: &str = "lid";
+pub const GROUP_SERVER: &str = "g.us";
+pub const NEWSLETTER_SERVER: &str = "newsletter";
+pub const BROADCAST_SERVER: &str = "broadcast";
+pub const HOSTED_SERVER: &str = "hosted";
+pub const HOSTED_LID_SERVER: &str = "hosted.lid";
+
+/// Mirrors Baileys' `WAJIDDomains`.
+const DOMAIN_WHATSAPP: u32 = 0;
+const DOMAIN_LID: u32 = 1;
+const DOMAIN_HOSTED: u32 = 128;
+const DOMAIN_HOSTED_LID: u32 = 129;
+
+/// Result of [`jid_decode`], shaped like Baileys' `FullJid`.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct FullJid {
+    pub user: String,
+    pub server: String,
+    /// `NaN` for a non-numeric agent, as in Baileys.
+    pub domain_type: f64,
+    /// `NaN` for a non-numeric device, as in Baileys.
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<f64>,
+}
+
+/// A parsed `user[_agent][:device]@server` address.
+#[wasm_bindgen]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Jid {
+    user: String,
+    server: String,
+    agent: Option<u32>,
+    device: Option<u32>,
+}
+
+thread_local! {
+    static JID_PROTOTYPE: Object =
+        prototype_of(Jid::new(String::new(), String::new(), None, None).into());
+}
+
+impl Jid {
+    /// The string form of `value` if it is a `Jid` instance.
+    pub(crate) fn string_from_js(value: &JsValue) -> Option<String> {
+        JID_PROTOTYPE
+            .with(|prototype| exported_ref::<Jid>(value, prototype).map(|jid| jid.to_string()))
+    }
+
+    pub(crate) fn parse_str(jid: &str) -> Option<Self> {
+        let (user_combined, server) = jid.split_once('@')?;
+        let (user_agent, device) = match user_combined.split_once(':') {
+            Some((user_agent, device)) => (user_agent, Some(device.parse().ok()?)),
+            None => (user_combined, None),
+        };
+        let (user, agent) = match user_agent.split_once('_') {
+            Some((user, agent)) => (user, Some(agent.parse().ok()?)),
+            None => (user_agent, None),
+        };
+
+        Some(Self {
+            user: user.to_string(),
+            server: server.to_string(),
+            agent,
+            device,
+        })
+    }
+
+    /// Inverse of [`Jid::to_signal_address_string`]: the device JID for a
+    /// libsignal `user[_domain]` name and device id.
+    pub(crate) fn from_signal_address(name: &str, device: u32) -> Self {
+        let (user, domain) = match name.rsplit_once('_') {
+            Some((user, domain)) => match domain.parse() {
+                Ok(domain) => (user, domain),
+                Err(_) => (name, DOMAIN_WHATSAPP),
+            },
+            None => (name, DOMAIN_WHATSAPP),
+        };
+        let (server, agent) = match domain {
+            DOMAIN_WHATSAPP => (DEFAULT_USER_SERVER, None),
+            DOMAIN_LID => (LID_SERVER, None),
+            DOMAIN_HOSTED => (HOSTED_SERVER, None),
+            DOMAIN_HOSTED_LID => (HOSTED_LID_SERVER, None),
+            agent => (DEFAULT_USER_SERVER, Some(agent)),
+        };
+
+        Self {
+            user: user.to_string(),
+            server: server.to_string(),
+            agent,
+            device: Some(device).filter(|&d| d != 0),
+        }
+    }
+
+    fn domain(&self) -> u32 {
+        match self.server.as_str() {
+            LID_SERVER => DOMAIN_LID,
+            HOSTED_SERVER => DOMAIN_HOSTED,
+            HOSTED_LID_SERVER => DOMAIN_HOSTED_LID,
+            _ => self.agent.unwrap_or(DOMAIN_WHATSAPP),
+        }
+    }
+
+    /// The libsignal user name: LID and hosted users carry their domain as a suffix.
+    fn signal_user(&self) -> String {
+        match self.domain() {
+            DOMAIN_WHATSAPP => self.user.clone(),
+            domain => format!("{}_{}", self.user, domain),
+        }
+    }
+
+    fn signal_device(&self) -> Result<u32, JsValue> {
+        let device = self.device.unwrap_or(0);
+        if device == 99 && !self.is_hosted() {
+            return Err(JsValue::from_str(&format!(
+                "Unexpected non-hosted device JID with device 99: {self}"
+            )));
+        }
+        Ok(device)
+    }
+}
+
+impl fmt::Display for Jid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.user)?;
+        if let Some(agent) = self.agent.filter(|&a| a != 0) {
+            write!(f, "_{agent}")?;
+        }
+        if let Some(device) = self.device.filter(|&d| d != 0) {
+            write!(f, ":{device}")?;
+        }
+        write!(f, "@{}", self.server)
+    }
+}
+
+#[wasm_bindgen]
+impl Jid {
+    #[wasm_bindgen(constructor)]
+    pub fn new(user: String, server: String, device: Option<u32>, agent: Option<u32>) -> Jid {
+        Jid {
+            user,
+            server,
+            agent,
+            device,
+        }
+    }
+
+    pub fn parse(jid: &str) -> Result<Jid, JsValue> {
+        Self::parse_str(jid).ok_or_else(|| JsValue::from_str(&format!("Invalid JID: {jid}")))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn user(&self) -> String {
+        self.user.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn server(&self) -> String {
+        self.server.clone()
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn device(&self) -> Option<u32> {
+        self.device
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn agent(&self) -> Option<u32> {
+        self.agent
+    }
+
+    #[wasm_bindgen(getter, js_name = domainType)]
+    pub fn domain_type(&self) -> u32 {
+        self.domain()
+    }
+
+    #[wasm_bindgen(js_name = isLid)]
+    pub fn is_lid(&self) -> bool {
+        matches!(self.server.as_str(), LID_SERVER | HOSTED_LID_SERVER)
+    }
+
+    #[wasm_bindgen(js_name = isGroup)]
+    pub fn is_group(&self) -> bool {
+        self.server == GROUP_SERVER
+    }
+
+    #[wasm_bindgen(js_name = isNewsletter)]
+    pub fn is_newsletter(&self) -> bool {
+        self.server == NEWSLETTER_SERVER
+    }
+
+    #[wasm_bindgen(js_name = isBroadcast)]
+    pub fn is_broadcast(&self) -> bool {
+        self.server == BROADCAST_SERVER
+    }
+
+    #[wasm_bindgen(js_name = isHosted)]
+    pub fn is_hosted(&self) -> bool {
+        matches!(self.server.as_str(), HOSTED_SERVER | HOSTED_LID_SERVER)
+    }
+
+    /// Same JID without the device part, e.g. for comparing senders.
+    #[wasm_bindgen(js_name = toUserJid)]
+    pub fn to_user_jid(&self) -> Jid {
+        Jid {
+            device: None,
+            ..self.clone()
+        }
+    }
+
+    #[wasm_bindgen(js_name = toProtocolAddress)]
+    pub fn to_protocol_address(&self) -> Result<ProtocolAddress, JsValue> {
+        Ok(ProtocolAddress(CoreProtocolAddress::new(
+            self.signal_user(),
+            DeviceId::from(self.signal_device()?),
+        )))
+    }
+
+    /// `user[_domain].device`, the key Baileys uses for session storage.
+    #[wasm_bindgen(js_name = toSignalAddressString)]
+    pub fn to_signal_address_string(&self) -> Result<String, JsValue> {
+        Ok(format!("{}.{}", self.signal_user(), self.signal_device()?))
+    }
+
+    pub fn equals(&self, other: &Jid) -> bool {
+        self == other
+    }
+
+    #[wasm_bindgen(js_name = toString)]
+    pub fn js_to_string(&self) -> String {
+        self.to_string()
+    }
+}
+
+/// Same contract as Baileys' `jidDecode`: `undefined` without an `@`, and the
+/// device and agent coerced like Baileys does (`NaN` when not numeric), where
+/// `Jid.parse` would throw.
+#[wasm_bindgen(js_name = jidDecode)]
+pub fn jid_decode(jid: &str) -> Option<FullJid> {
+    let (user_combined, server) = jid.split_once('@')?;
+    let mut user_device = user_combined.split(':');
+    let user_agent = user_device.next().unwrap_or_default();
+    let device = user_device
+        .next()
+        .filter(|d| !d.is_empty())
+        .map(|d| Number::new(&JsValue::from_str(d)).value_of());
+    let mut user_agent = user_agent.split('_');
+    let user = user_agent.next().unwrap_or_default();
+    let agent = user_agent.next().filter(|a| !a.is_empty());
+
+    let domain_type = match server {
+        LID_SERVER => DOMAIN_LID as f64,
+        HOSTED_SERVER => DOMAIN_HOSTED as f64,
+        HOSTED_LID_SERVER => DOMAIN_HOSTED_LID as f64,
+        _ => agent.map_or(DOMAIN_WHATSAPP as f64, |a| Number::parse_int(a, 10)),
+    };
+
+    Some(FullJid {
+        user: user.to_string(),
+        server: server.to_string(),
+        domain_type,
+        device,
+    })
+}
+
+#[wasm_bindgen(js_name = jidEncode)]
+pub fn jid_encode(
+    user: Option<String>,
+    server: String,
+    device: Option<u32>,
+    agent: Option<u32>,
+) -> String {
+    Jid::new(user.unwrap_or_default(), server, device, agent).to_string()
+}
@@ -0,0 +1,18 @@
+use wasm_bindgen::prelude::*;
+
+/// The message of a thrown JS value: a string, an `Error`'s `message`, or
+/// its debug form.
+pub(crate) fn error_message(e: &JsValue) -> String {
+    e.as_string()
+        .or_else(|| e.dyn_ref::<js_sys::Error>()?.message().as_string())
+        .unwrap_or_else(|| format!("{e:?}"))
+}
+
+/// An `Error` with `name` set and one extra string property, so JS callers
+/// can tell error kinds apart without parsing messages.
+pub(crate) fn typed_js_error(name: &str, field: &str, value: &str, message: &str) -> JsValue {
+    let err = js_sys::Error::new(message);
+    err.set_name(name);
+    let _ = js_sys::Reflect::set(&err, &JsValue::from_str(field), &JsValue::from_str(value));
+    err.into()
+}
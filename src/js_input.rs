@@ -0,0 +1,53 @@
+use js_sys::{ArrayBuffer, Reflect, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::JsFuture;
+use web_sys::{ReadableStream, ReadableStreamDefaultReader};
+
+/// Calls `on_chunk` for every chunk of a Uint8Array, ArrayBuffer or ReadableStream
+/// without collecting the stream into memory first.
+pub(crate) async fn for_each_input_chunk(
+    input: JsValue,
+    mut on_chunk: impl FnMut(&[u8]) -> Result<(), JsValue>,
+) -> Result<(), JsValue> {
+    if input.is_instance_of::<Uint8Array>() || input.is_instance_of::<ArrayBuffer>() {
+        return on_chunk(&Uint8Array::new(&input).to_vec());
+    }
+
+    if !input.is_instance_of::<ReadableStream>() {
+        return Err(JsValue::from_str(
+            "Unsupported input type. Expected Uint8Array, ArrayBuffer, or ReadableStream",
+        ));
+    }
+
+    let reader = input
+        .unchecked_ref::<ReadableStream>()
+        .get_reader()
+        .unchecked_into::<ReadableStreamDefaultReader>();
+    let done_key = JsValue::from_str("done");
+    let value_key = JsValue::from_str("value");
+    let mut buffer = Vec::with_capacity(64 * 1024);
+
+    loop {
+        let result = JsFuture::from(reader.read()).await?;
+
+        if Reflect::get(&result, &done_key)?.as_bool().unwrap_or(false) {
+            break;
+        }
+
+        let value = Reflect::get(&result, &value_key)?;
+        if value.is_undefined() || value.is_null() {
+            continue;
+        }
+
+        let chunk = Uint8Array::new(&value);
+        buffer.resize(chunk.length() as usize, 0);
+        chunk.copy_to(&mut buffer);
+        if let Err(e) = on_chunk(&buffer) {
+            let _ = reader.cancel_with_reason(&e);
+            return Err(e);
+        }
+    }
+
+    reader.release_lock();
+    Ok(())
+}
@@ -4,18 +4,37 @@ pub mod audio;
 pub mod binary;
 pub mod crypto;
 pub mod curve;
+pub mod decode_limits;
+mod exported;
+pub mod frame_limits;
 pub mod group_cipher;
 pub mod group_types;
 #[cfg(feature = "image")]
 pub mod image_utils;
+pub mod jid;
+mod js_error;
+mod js_input;
 pub mod key_helper;
 pub mod logger;
+pub mod media_crypto;
+pub mod media_sidecar;
+pub mod message_decryptor;
+pub mod message_encryptor;
+pub mod node_builder;
+pub mod node_diff;
+pub mod node_json;
+pub mod node_xml;
+pub mod noise_cert;
 pub mod noise_session;
+pub mod noise_state;
+pub mod padding;
+pub mod proto;
 pub mod protocol_address;
 pub mod sender_key_name;
 pub mod session_builder;
 pub mod session_cipher;
 pub mod session_record;
+pub mod stanza;
 #[cfg(feature = "sticker")]
 pub mod sticker_metadata;
 pub mod storage_adapter;
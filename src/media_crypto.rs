@@ -0,0 +1,527 @@
+use aes::Aes256;
+use aes::cipher::{BlockCipherDecrypt, BlockCipherEncrypt, KeyInit};
+use hmac::{Hmac, Mac};
+use js_sys::Uint8Array;
+use rand::{Rng, rngs::StdRng};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::mem;
+use tsify_next::Tsify;
+use wasm_bindgen::prelude::*;
+
+use crate::crypto::hkdf_expand;
+use crate::js_input::for_each_input_chunk;
+use crate::media_sidecar::MediaSidecarGenerator;
+
+pub(crate) type HmacSha256 = Hmac<Sha256>;
+
+const MEDIA_KEY_LENGTH: usize = 32;
+/// iv (16) + cipherKey (32) + macKey (32) + refKey (32)
+const EXPANDED_KEY_LENGTH: usize = 112;
+const BLOCK_SIZE: usize = 16;
+/// WhatsApp appends the first 10 bytes of the HMAC-SHA256 to the ciphertext.
+const MAC_LENGTH: usize = 10;
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_MEDIA_TYPE: &str = r#"
+export type MediaType =
+    | "image"
+    | "video"
+    | "audio"
+    | "document"
+    | "sticker"
+    | "gif"
+    | "ptt"
+    | "ptv"
+    | "product"
+    | "biz-cover-photo"
+    | "thumbnail-image"
+    | "thumbnail-video"
+    | "thumbnail-document"
+    | "thumbnail-link"
+    | "md-msg-hist"
+    | "md-app-state"
+    | "payment-bg-image";
+
+export type MediaInput =
+    | Uint8Array
+    | ArrayBuffer
+    | ReadableStream<Uint8Array | ArrayBuffer | ArrayBufferView>;
+"#;
+
+/// Maps a media type to the HKDF info string WhatsApp uses to expand its mediaKey.
+fn hkdf_info(media_type: &str) -> Result<&'static str, JsValue> {
+    Ok(match media_type {
+        "image" | "sticker" | "product" | "biz-cover-photo" => "WhatsApp Image Keys",
+        "video" | "gif" | "ptv" => "WhatsApp Video Keys",
+        "audio" | "ptt" => "WhatsApp Audio Keys",
+        "document" => "WhatsApp Document Keys",
+        "thumbnail-image" => "WhatsApp Image Thumbnail Keys",
+        "thumbnail-video" => "WhatsApp Video Thumbnail Keys",
+        "thumbnail-document" => "WhatsApp Document Thumbnail Keys",
+        "thumbnail-link" => "WhatsApp Link Thumbnail Keys",
+        "md-msg-hist" => "WhatsApp History Keys",
+        "md-app-state" => "WhatsApp App State Keys",
+        "payment-bg-image" => "WhatsApp Payment Background Keys",
+        other => {
+            return Err(JsValue::from_str(&format!(
+                "Unsupported media type: {other}"
+            )));
+        }
+    })
+}
+
+/// Keys expanded from a 32-byte mediaKey.
+pub(crate) struct MediaKeys {
+    pub iv: [u8; BLOCK_SIZE],
+    pub cipher_key: [u8; 32],
+    pub mac_key: [u8; 32],
+    pub ref_key: [u8; 32],
+}
+
+impl MediaKeys {
+    pub(crate) fn derive(media_key: &[u8], media_type: &str) -> Result<Self, JsValue> {
+        if media_key.len() != MEDIA_KEY_LENGTH {
+            return Err(JsValue::from_str(&format!(
+                "mediaKey must be {} bytes, got {}",
+                MEDIA_KEY_LENGTH,
+                media_key.len()
+            )));
+        }
+
+        let info = hkdf_info(media_type)?;
+        let expanded = hkdf_expand(media_key, None, info.as_bytes(), EXPANDED_KEY_LENGTH)?;
+
+        let mut keys = MediaKeys {
+            iv: [0; BLOCK_SIZE],
+            cipher_key: [0; 32],
+            mac_key: [0; 32],
+            ref_key: [0; 32],
+        };
+        keys.iv.copy_from_slice(&expanded[..16]);
+        keys.cipher_key.copy_from_slice(&expanded[16..48]);
+        keys.mac_key.copy_from_slice(&expanded[48..80]);
+        keys.ref_key.copy_from_slice(&expanded[80..112]);
+        Ok(keys)
+    }
+
+    fn cipher(&self) -> Aes256 {
+        Aes256::new_from_slice(&self.cipher_key).expect("cipher key is 32 bytes")
+    }
+
+    pub(crate) fn mac(&self) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.mac_key).expect("HMAC accepts keys of any size");
+        mac.update(&self.iv);
+        mac
+    }
+}
+
+/// Expanded media keys, as returned by Baileys' `getMediaKeys`.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ExpandedMediaKeys {
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub iv: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub cipher_key: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub mac_key: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub ref_key: Vec<u8>,
+}
+
+#[wasm_bindgen(js_name = getMediaKeys)]
+pub fn get_media_keys(media_key: &[u8], media_type: &str) -> Result<ExpandedMediaKeys, JsValue> {
+    let keys = MediaKeys::derive(media_key, media_type)?;
+    Ok(ExpandedMediaKeys {
+        iv: keys.iv.to_vec(),
+        cipher_key: keys.cipher_key.to_vec(),
+        mac_key: keys.mac_key.to_vec(),
+        ref_key: keys.ref_key.to_vec(),
+    })
+}
+
+/// Result of finishing a [`MediaEncryptor`].
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct MediaEncryptionResult {
+    /// Last ciphertext bytes: the padded final block followed by the truncated MAC.
+    /// Append this to the chunks returned by `update` to get the full upload body.
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub tail: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub media_key: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub file_sha256: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub file_enc_sha256: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub mac: Vec<u8>,
+    pub file_length: u64,
+    /// Present when the encryptor was created with `streamingSidecar` enabled.
+    #[tsify(optional, type = "Uint8Array")]
+    #[serde(with = "serde_bytes", skip_serializing_if = "Option::is_none")]
+    pub streaming_sidecar: Option<Vec<u8>>,
+}
+
+/// Result of [`encrypt_media`], with the whole ciphertext in one buffer.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptedMedia {
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub ciphertext: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub media_key: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub file_sha256: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub file_enc_sha256: Vec<u8>,
+    pub file_length: u64,
+    #[tsify(optional, type = "Uint8Array")]
+    #[serde(with = "serde_bytes", skip_serializing_if = "Option::is_none")]
+    pub streaming_sidecar: Option<Vec<u8>>,
+}
+
+#[inline]
+fn cbc_encrypt_blocks(cipher: &Aes256, chain: &mut [u8; BLOCK_SIZE], data: &mut [u8]) {
+    let mut block = aes::Block::default();
+    for chunk in data.chunks_exact_mut(BLOCK_SIZE) {
+        for (b, c) in chunk.iter_mut().zip(chain.iter()) {
+            *b ^= c;
+        }
+        block.copy_from_slice(chunk);
+        cipher.encrypt_block(&mut block);
+        chunk.copy_from_slice(&block);
+        chain.copy_from_slice(chunk);
+    }
+}
+
+#[inline]
+fn cbc_decrypt_blocks(cipher: &Aes256, chain: &mut [u8; BLOCK_SIZE], data: &mut [u8]) {
+    let mut block = aes::Block::default();
+    let mut next_chain = [0u8; BLOCK_SIZE];
+    for chunk in data.chunks_exact_mut(BLOCK_SIZE) {
+        next_chain.copy_from_slice(chunk);
+        block.copy_from_slice(chunk);
+        cipher.decrypt_block(&mut block);
+        for ((out, b), c) in chunk.iter_mut().zip(block.iter()).zip(chain.iter()) {
+            *out = b ^ c;
+        }
+        *chain = next_chain;
+    }
+}
+
+#[inline]
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// Incremental AES-256-CBC + HMAC-SHA256 encryptor for WhatsApp media uploads.
+///
+/// Feed plaintext with `update` (each call returns the ciphertext produced so far)
+/// and call `finalize` once to get the trailing bytes and the upload hashes.
+#[wasm_bindgen]
+pub struct MediaEncryptor {
+    media_key: [u8; MEDIA_KEY_LENGTH],
+    cipher: Aes256,
+    chain: [u8; BLOCK_SIZE],
+    pending: Vec<u8>,
+    mac: HmacSha256,
+    file_sha256: Sha256,
+    file_enc_sha256: Sha256,
+    file_length: u64,
+    sidecar: Option<MediaSidecarGenerator>,
+    finished: bool,
+}
+
+impl MediaEncryptor {
+    pub(crate) fn with_key(
+        media_key: [u8; MEDIA_KEY_LENGTH],
+        media_type: &str,
+        streaming_sidecar: bool,
+    ) -> Result<Self, JsValue> {
+        let keys = MediaKeys::derive(&media_key, media_type)?;
+        Ok(Self {
+            sidecar: streaming_sidecar.then(|| MediaSidecarGenerator::from_keys(&keys)),
+            media_key,
+            cipher: keys.cipher(),
+            chain: keys.iv,
+            pending: Vec::with_capacity(BLOCK_SIZE),
+            mac: keys.mac(),
+            file_sha256: Sha256::new(),
+            file_enc_sha256: Sha256::new(),
+            file_length: 0,
+            finished: false,
+        })
+    }
+
+    fn ensure_active(&self) -> Result<(), JsValue> {
+        if self.finished {
+            return Err(JsValue::from_str("MediaEncryptor already finalized"));
+        }
+        Ok(())
+    }
+
+    fn absorb_ciphertext(&mut self, ciphertext: &[u8]) {
+        self.mac.update(ciphertext);
+        self.file_enc_sha256.update(ciphertext);
+        if let Some(sidecar) = self.sidecar.as_mut() {
+            sidecar.absorb(ciphertext);
+        }
+    }
+
+    /// Encrypts every complete block buffered so far and returns the ciphertext.
+    pub(crate) fn encrypt_chunk(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.file_sha256.update(chunk);
+        self.file_length += chunk.len() as u64;
+
+        let mut out = mem::take(&mut self.pending);
+        out.extend_from_slice(chunk);
+        let remainder = out.len() % BLOCK_SIZE;
+        self.pending = out.split_off(out.len() - remainder);
+
+        cbc_encrypt_blocks(&self.cipher, &mut self.chain, &mut out);
+        self.absorb_ciphertext(&out);
+        out
+    }
+
+    pub(crate) fn finish(&mut self) -> MediaEncryptionResult {
+        self.finished = true;
+
+        let pad = (BLOCK_SIZE - self.pending.len()) as u8;
+        let mut tail = mem::take(&mut self.pending);
+        tail.resize(BLOCK_SIZE, pad);
+        cbc_encrypt_blocks(&self.cipher, &mut self.chain, &mut tail);
+        self.absorb_ciphertext(&tail);
+
+        let mac = self.mac.clone().finalize().into_bytes();
+        let mac = &mac[..MAC_LENGTH];
+        tail.extend_from_slice(mac);
+        self.file_enc_sha256.update(mac);
+
+        let streaming_sidecar = self.sidecar.as_mut().map(|sidecar| {
+            sidecar.absorb(mac);
+            sidecar.finish()
+        });
+
+        MediaEncryptionResult {
+            tail,
+            media_key: self.media_key.to_vec(),
+            file_sha256: self.file_sha256.clone().finalize().to_vec(),
+            file_enc_sha256: self.file_enc_sha256.clone().finalize().to_vec(),
+            mac: mac.to_vec(),
+            file_length: self.file_length,
+            streaming_sidecar,
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl MediaEncryptor {
+    /// Creates an encryptor. A random mediaKey is generated when none is given.
+    /// Pass `streamingSidecar = true` for seekable video/audio uploads.
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        media_type: &str,
+        media_key: Option<Vec<u8>>,
+        streaming_sidecar: Option<bool>,
+    ) -> Result<MediaEncryptor, JsValue> {
+        let media_key = match media_key {
+            Some(key) => <[u8; MEDIA_KEY_LENGTH]>::try_from(key.as_slice()).map_err(|_| {
+                JsValue::from_str(&format!(
+                    "mediaKey must be {} bytes, got {}",
+                    MEDIA_KEY_LENGTH,
+                    key.len()
+                ))
+            })?,
+            None => {
+                let mut key = [0u8; MEDIA_KEY_LENGTH];
+                rand::make_rng::<StdRng>().fill_bytes(&mut key);
+                key
+            }
+        };
+
+        Self::with_key(media_key, media_type, streaming_sidecar.unwrap_or(false))
+    }
+
+    #[wasm_bindgen(getter, js_name = mediaKey)]
+    pub fn media_key(&self) -> Uint8Array {
+        Uint8Array::from(self.media_key.as_slice())
+    }
+
+    /// Encrypts a plaintext chunk. May return fewer bytes than given (or none)
+    /// while a partial block is buffered.
+    pub fn update(&mut self, chunk: &[u8]) -> Result<Uint8Array, JsValue> {
+        self.ensure_active()?;
+        let ciphertext = self.encrypt_chunk(chunk);
+        Ok(Uint8Array::from(ciphertext.as_slice()))
+    }
+
+    pub fn finalize(&mut self) -> Result<MediaEncryptionResult, JsValue> {
+        self.ensure_active()?;
+        Ok(self.finish())
+    }
+}
+
+/// Incremental decryptor for WhatsApp media downloads.
+///
+/// Plaintext returned by `update` is not authenticated until `finalize`
+/// succeeds; callers must discard it if `finalize` throws.
+#[wasm_bindgen]
+pub struct MediaDecryptor {
+    cipher: Aes256,
+    chain: [u8; BLOCK_SIZE],
+    mac: HmacSha256,
+    buffered: Vec<u8>,
+    finished: bool,
+}
+
+impl MediaDecryptor {
+    /// Decrypts all buffered ciphertext except the bytes that may still be the
+    /// final (padded) block or the trailing MAC.
+    pub(crate) fn decrypt_chunk(&mut self, chunk: &[u8]) -> Vec<u8> {
+        self.buffered.extend_from_slice(chunk);
+
+        let available = self.buffered.len().saturating_sub(MAC_LENGTH + BLOCK_SIZE);
+        let ready = available - available % BLOCK_SIZE;
+        if ready == 0 {
+            return Vec::new();
+        }
+
+        let rest = self.buffered.split_off(ready);
+        let mut out = mem::replace(&mut self.buffered, rest);
+        self.mac.update(&out);
+        cbc_decrypt_blocks(&self.cipher, &mut self.chain, &mut out);
+        out
+    }
+
+    pub(crate) fn finish(&mut self) -> Result<Vec<u8>, JsValue> {
+        self.finished = true;
+
+        let len = self.buffered.len();
+        if len < MAC_LENGTH + BLOCK_SIZE || !(len - MAC_LENGTH).is_multiple_of(BLOCK_SIZE) {
+            return Err(JsValue::from_str("Invalid media ciphertext length"));
+        }
+
+        let mut out = mem::take(&mut self.buffered);
+        let expected_mac = out.split_off(len - MAC_LENGTH);
+        self.mac.update(&out);
+        let mac = self.mac.clone().finalize().into_bytes();
+        if !constant_time_eq(&mac[..MAC_LENGTH], &expected_mac) {
+            return Err(JsValue::from_str("Media MAC mismatch"));
+        }
+
+        cbc_decrypt_blocks(&self.cipher, &mut self.chain, &mut out);
+
+        let pad = out.last().copied().unwrap_or(0) as usize;
+        if pad == 0 || pad > BLOCK_SIZE || out[out.len() - pad..].iter().any(|&b| b as usize != pad)
+        {
+            return Err(JsValue::from_str("Invalid media padding"));
+        }
+        out.truncate(out.len() - pad);
+        Ok(out)
+    }
+}
+
+#[wasm_bindgen]
+impl MediaDecryptor {
+    #[wasm_bindgen(constructor)]
+    pub fn new(media_key: &[u8], media_type: &str) -> Result<MediaDecryptor, JsValue> {
+        let keys = MediaKeys::derive(media_key, media_type)?;
+        Ok(Self {
+            cipher: keys.cipher(),
+            chain: keys.iv,
+            mac: keys.mac(),
+            buffered: Vec::with_capacity(MAC_LENGTH + 2 * BLOCK_SIZE),
+            finished: false,
+        })
+    }
+
+    pub fn update(&mut self, chunk: &[u8]) -> Result<Uint8Array, JsValue> {
+        if self.finished {
+            return Err(JsValue::from_str("MediaDecryptor already finalized"));
+        }
+        let plaintext = self.decrypt_chunk(chunk);
+        Ok(Uint8Array::from(plaintext.as_slice()))
+    }
+
+    /// Verifies the MAC, strips the padding and returns the remaining plaintext.
+    pub fn finalize(&mut self) -> Result<Uint8Array, JsValue> {
+        if self.finished {
+            return Err(JsValue::from_str("MediaDecryptor already finalized"));
+        }
+        let plaintext = self.finish()?;
+        Ok(Uint8Array::from(plaintext.as_slice()))
+    }
+}
+
+#[wasm_bindgen(js_name = encryptMedia, skip_typescript)]
+pub async fn encrypt_media(
+    input: JsValue,
+    media_type: String,
+    media_key: Option<Vec<u8>>,
+    streaming_sidecar: Option<bool>,
+) -> Result<EncryptedMedia, JsValue> {
+    let mut encryptor = MediaEncryptor::new(&media_type, media_key, streaming_sidecar)?;
+    let mut ciphertext = Vec::new();
+
+    for_each_input_chunk(input, |chunk| {
+        ciphertext.extend_from_slice(&encryptor.encrypt_chunk(chunk));
+        Ok(())
+    })
+    .await?;
+
+    let result = encryptor.finish();
+    ciphertext.extend_from_slice(&result.tail);
+
+    Ok(EncryptedMedia {
+        ciphertext,
+        media_key: result.media_key,
+        file_sha256: result.file_sha256,
+        file_enc_sha256: result.file_enc_sha256,
+        file_length: result.file_length,
+        streaming_sidecar: result.streaming_sidecar,
+    })
+}
+
+#[wasm_bindgen(js_name = decryptMedia, skip_typescript)]
+pub async fn decrypt_media(
+    input: JsValue,
+    media_key: Vec<u8>,
+    media_type: String,
+) -> Result<Uint8Array, JsValue> {
+    let mut decryptor = MediaDecryptor::new(&media_key, &media_type)?;
+    let mut plaintext = Vec::new();
+
+    for_each_input_chunk(input, |chunk| {
+        plaintext.extend_from_slice(&decryptor.decrypt_chunk(chunk));
+        Ok(())
+    })
+    .await?;
+
+    plaintext.extend_from_slice(&decryptor.finish()?);
+    Ok(Uint8Array::from(plaintext.as_slice()))
+}
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_MEDIA_FUNCTIONS: &str = r#"
+export function encryptMedia(input: MediaInput, mediaType: MediaType, mediaKey?: Uint8Array, streamingSidecar?: boolean): Promise<EncryptedMedia>;
+export function decryptMedia(input: MediaInput, mediaKey: Uint8Array, mediaType: MediaType): Promise<Uint8Array>;
+"#;
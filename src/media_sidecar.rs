@@ -0,0 +1,162 @@
+use hmac::Mac;
+use js_sys::Uint8Array;
+use wasm_bindgen::prelude::*;
+
+use crate::media_crypto::{HmacSha256, MediaKeys};
+
+/// Sidecar entries cover 64 KiB of the `iv || enc` stream...
+const SIDECAR_CHUNK_SIZE: usize = 64 * 1024;
+/// ...plus the following AES block, so each window can be decrypted on its own.
+const SIDECAR_WINDOW_SIZE: usize = SIDECAR_CHUNK_SIZE + 16;
+const SIDECAR_MAC_LENGTH: usize = 10;
+
+#[inline]
+fn sign_window(mac: &HmacSha256, window: &[u8]) -> [u8; SIDECAR_MAC_LENGTH] {
+    let mut mac = mac.clone();
+    mac.update(window);
+    let digest = mac.finalize().into_bytes();
+    let mut out = [0u8; SIDECAR_MAC_LENGTH];
+    out.copy_from_slice(&digest[..SIDECAR_MAC_LENGTH]);
+    out
+}
+
+/// Builds the `streamingSidecar` for seekable video/audio.
+///
+/// The sidecar is the concatenation of `HMAC-SHA256(macKey, window)[..10]` for
+/// every 64 KiB step over `iv || enc`, where each window extends 16 bytes into
+/// the next chunk. Feed it the same encrypted chunks that are uploaded.
+#[wasm_bindgen]
+pub struct MediaSidecarGenerator {
+    mac: HmacSha256,
+    pending: Vec<u8>,
+    sidecar: Vec<u8>,
+    finished: bool,
+}
+
+impl MediaSidecarGenerator {
+    pub(crate) fn from_keys(keys: &MediaKeys) -> Self {
+        let mut generator = Self {
+            mac: HmacSha256::new_from_slice(&keys.mac_key).expect("HMAC accepts keys of any size"),
+            pending: Vec::with_capacity(SIDECAR_WINDOW_SIZE),
+            sidecar: Vec::new(),
+            finished: false,
+        };
+        generator.absorb(&keys.iv);
+        generator
+    }
+
+    pub(crate) fn absorb(&mut self, encrypted: &[u8]) {
+        self.pending.extend_from_slice(encrypted);
+
+        let mut start = 0;
+        while self.pending.len() - start >= SIDECAR_WINDOW_SIZE {
+            let entry = sign_window(&self.mac, &self.pending[start..start + SIDECAR_WINDOW_SIZE]);
+            self.sidecar.extend_from_slice(&entry);
+            start += SIDECAR_CHUNK_SIZE;
+        }
+        self.pending.drain(..start);
+    }
+
+    pub(crate) fn finish(&mut self) -> Vec<u8> {
+        self.finished = true;
+
+        let mut start = 0;
+        while start < self.pending.len() {
+            let end = (start + SIDECAR_WINDOW_SIZE).min(self.pending.len());
+            let entry = sign_window(&self.mac, &self.pending[start..end]);
+            self.sidecar.extend_from_slice(&entry);
+            start += SIDECAR_CHUNK_SIZE;
+        }
+        self.pending.clear();
+
+        std::mem::take(&mut self.sidecar)
+    }
+}
+
+#[wasm_bindgen]
+impl MediaSidecarGenerator {
+    #[wasm_bindgen(constructor)]
+    pub fn new(media_key: &[u8], media_type: &str) -> Result<MediaSidecarGenerator, JsValue> {
+        let keys = MediaKeys::derive(media_key, media_type)?;
+        Ok(Self::from_keys(&keys))
+    }
+
+    /// Feeds encrypted bytes (ciphertext and trailing MAC) in upload order.
+    pub fn update(&mut self, encrypted_chunk: &[u8]) -> Result<(), JsValue> {
+        if self.finished {
+            return Err(JsValue::from_str("MediaSidecarGenerator already finalized"));
+        }
+        self.absorb(encrypted_chunk);
+        Ok(())
+    }
+
+    pub fn finalize(&mut self) -> Result<Uint8Array, JsValue> {
+        if self.finished {
+            return Err(JsValue::from_str("MediaSidecarGenerator already finalized"));
+        }
+        Ok(Uint8Array::from(self.finish().as_slice()))
+    }
+}
+
+/// Verifies a downloaded byte range of an encrypted media file against its sidecar.
+///
+/// `range_offset` is the offset of `encrypted_range` inside the encrypted file. It
+/// must be `0` or 16 bytes before a 64 KiB boundary, so that every window starts
+/// with the block that serves as its IV. The range must end on a window boundary
+/// or at the end of the file. Returns `false` on the first mismatching window.
+#[wasm_bindgen(js_name = verifyMediaSidecar)]
+pub fn verify_media_sidecar(
+    media_key: &[u8],
+    media_type: &str,
+    sidecar: &[u8],
+    encrypted_range: &[u8],
+    range_offset: u32,
+) -> Result<bool, JsValue> {
+    if !sidecar.len().is_multiple_of(SIDECAR_MAC_LENGTH) {
+        return Err(JsValue::from_str(&format!(
+            "Sidecar length must be a multiple of {}, got {}",
+            SIDECAR_MAC_LENGTH,
+            sidecar.len()
+        )));
+    }
+
+    let keys = MediaKeys::derive(media_key, media_type)?;
+
+    // Window `k` starts at offset `k * 64 KiB` of the `iv || enc` stream.
+    let stream_offset = range_offset as usize + keys.iv.len();
+    let with_iv;
+    let (stream, first_window): (&[u8], usize) = if range_offset == 0 {
+        with_iv = [keys.iv.as_slice(), encrypted_range].concat();
+        (&with_iv, 0)
+    } else if stream_offset.is_multiple_of(SIDECAR_CHUNK_SIZE) {
+        (encrypted_range, stream_offset / SIDECAR_CHUNK_SIZE)
+    } else {
+        return Err(JsValue::from_str(
+            "rangeOffset must be 0 or 16 bytes before a 64 KiB chunk boundary",
+        ));
+    };
+
+    let mac = HmacSha256::new_from_slice(&keys.mac_key).expect("HMAC accepts keys of any size");
+    let mut start = 0;
+    let mut index = first_window;
+    while start < stream.len() {
+        let end = (start + SIDECAR_WINDOW_SIZE).min(stream.len());
+        let expected_start = index * SIDECAR_MAC_LENGTH;
+        let Some(expected) = sidecar.get(expected_start..expected_start + SIDECAR_MAC_LENGTH)
+        else {
+            return Ok(false);
+        };
+
+        if sign_window(&mac, &stream[start..end]) != expected {
+            return Ok(false);
+        }
+
+        if end == stream.len() {
+            break;
+        }
+        start += SIDECAR_CHUNK_SIZE;
+        index += 1;
+    }
+
+    Ok(true)
+}
@@ -0,0 +1,213 @@
+use prost::Message as _;
+use serde::Serialize;
+use tsify_next::Tsify;
+use wacore_libsignal::protocol::{
+    SenderKeyDistributionMessage as CoreSenderKeyDistributionMessage, group_decrypt,
+    process_sender_key_distribution_message,
+};
+use waproto::whatsapp::Message;
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{EncodingNode, js_to_node_ref};
+use crate::jid::Jid;
+use crate::js_error::error_message;
+use crate::padding::unpad_lenient;
+use crate::proto::from_proto;
+use crate::protocol_address::ProtocolAddress;
+use crate::sender_key_name::SenderKeyName;
+use crate::session_cipher::{decrypt_prekey_message, decrypt_signal_message};
+use crate::stanza::{EncChild, message_stanza};
+use crate::storage_adapter::{JsStorageAdapter, SignalStorage};
+
+/// Outcome of one `<enc>` child. Exactly one of `message`/`error` is set,
+/// except when the message decrypted but its sender key distribution could
+/// not be processed, in which case both are.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct DecryptedEnc {
+    #[serde(rename = "type")]
+    pub enc_type: String,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub v: Option<u32>,
+    #[tsify(optional, type = "WAMessage")]
+    #[serde(
+        with = "serde_wasm_bindgen::preserve",
+        skip_serializing_if = "JsValue::is_undefined"
+    )]
+    pub message: JsValue,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct DecryptedMessage {
+    pub id: String,
+    /// Chat the message belongs to (`from` of the stanza).
+    pub chat: String,
+    /// Sender whose session or sender key was used (`participant`, else `from`).
+    pub author: String,
+    pub results: Vec<DecryptedEnc>,
+}
+
+/// Decrypts inbound `<message>` stanzas: dispatches each `<enc>` to the
+/// session or group cipher, strips the random padding, decodes the `Message`
+/// proto and processes any sender key distribution it carries.
+#[wasm_bindgen(js_name = MessageDecryptor)]
+pub struct MessageDecryptor {
+    storage_adapter: JsStorageAdapter,
+}
+
+impl MessageDecryptor {
+    async fn decrypt_plaintext(
+        &mut self,
+        enc: &EncChild,
+        chat: &str,
+        author: &ProtocolAddress,
+    ) -> Result<Vec<u8>, String> {
+        match enc.enc_type.as_str() {
+            "pkmsg" => {
+                decrypt_prekey_message(&self.storage_adapter, &author.0, &enc.ciphertext).await
+            }
+            "msg" => {
+                decrypt_signal_message(&self.storage_adapter, &author.0, &enc.ciphertext).await
+            }
+            "skmsg" => {
+                let sender_key_name = SenderKeyName::new(chat.to_string(), author);
+                group_decrypt(
+                    &enc.ciphertext,
+                    &mut self.storage_adapter,
+                    &sender_key_name.0,
+                )
+                .await
+                .map_err(|e| e.to_string())
+            }
+            "plaintext" => Ok(enc.ciphertext.clone()),
+            "msmsg" => Err("unsupported: msmsg".to_string()),
+            other => Err(format!("Unknown enc type \"{other}\"")),
+        }
+    }
+
+    async fn process_sender_key(
+        &mut self,
+        message: &Message,
+        author: &ProtocolAddress,
+    ) -> Result<(), String> {
+        let Some(skdm) = &message.sender_key_distribution_message else {
+            return Ok(());
+        };
+        let (Some(group_id), Some(serialized)) = (
+            &skdm.group_id,
+            &skdm.axolotl_sender_key_distribution_message,
+        ) else {
+            return Ok(());
+        };
+
+        let skdm = CoreSenderKeyDistributionMessage::try_from(serialized.as_slice())
+            .map_err(|e| format!("Invalid SenderKeyDistributionMessage: {e}"))?;
+        let sender_key_name = SenderKeyName::new(group_id.clone(), author);
+        process_sender_key_distribution_message(
+            &sender_key_name.0,
+            &skdm,
+            &mut self.storage_adapter,
+        )
+        .await
+        .map_err(|e| format!("Failed to process SenderKeyDistributionMessage: {e}"))
+    }
+
+    async fn decrypt_enc(
+        &mut self,
+        enc: &EncChild,
+        chat: &str,
+        author: &ProtocolAddress,
+    ) -> DecryptedEnc {
+        let mut result = DecryptedEnc {
+            enc_type: enc.enc_type.clone(),
+            v: enc.v,
+            message: JsValue::UNDEFINED,
+            error: None,
+        };
+
+        let decoded = match self.decrypt_plaintext(enc, chat, author).await {
+            Ok(plaintext) if enc.enc_type == "plaintext" => Message::decode(plaintext.as_slice()),
+            Ok(plaintext) => match unpad_lenient(&plaintext) {
+                Ok(unpadded) => Message::decode(unpadded),
+                Err(e) => {
+                    result.error = Some(e.to_string());
+                    return result;
+                }
+            },
+            Err(e) => {
+                result.error = Some(e);
+                return result;
+            }
+        };
+        let mut message = match decoded {
+            Ok(message) => message,
+            Err(e) => {
+                result.error = Some(format!("Failed to decode Message: {e}"));
+                return result;
+            }
+        };
+
+        let device_sent = message
+            .device_sent_message
+            .as_ref()
+            .and_then(|dsm| dsm.message.as_ref())
+            .map(|inner| Message::clone(inner));
+        if let Some(inner) = device_sent {
+            message = inner;
+        }
+
+        if let Err(e) = self.process_sender_key(&message, author).await {
+            result.error = Some(e);
+        }
+        match from_proto(&message, "Message") {
+            Ok(message) => result.message = message,
+            Err(e) => result.error = Some(error_message(&e)),
+        }
+        result
+    }
+}
+
+#[wasm_bindgen(js_class = MessageDecryptor)]
+impl MessageDecryptor {
+    #[wasm_bindgen(constructor)]
+    pub fn new(storage: SignalStorage) -> Self {
+        Self {
+            storage_adapter: JsStorageAdapter::new(storage),
+        }
+    }
+
+    /// Decrypts every `<enc>` child of a `<message>` stanza in order. Failures
+    /// are reported per child; only a malformed stanza rejects.
+    pub async fn decrypt(&mut self, stanza: EncodingNode) -> Result<DecryptedMessage, JsValue> {
+        let node = js_to_node_ref(&stanza)?;
+        let stanza = message_stanza(&node)?;
+
+        let chat = stanza.from.jid;
+        let author = stanza
+            .participant
+            .map(|p| p.jid)
+            .unwrap_or_else(|| chat.clone());
+        let address = Jid::parse_str(&author)
+            .ok_or_else(|| JsValue::from_str(&format!("Invalid author JID: {author}")))?
+            .to_protocol_address()?;
+
+        let mut results = Vec::with_capacity(stanza.enc.len());
+        for enc in &stanza.enc {
+            results.push(self.decrypt_enc(enc, &chat, &address).await);
+        }
+
+        Ok(DecryptedMessage {
+            id: stanza.id,
+            chat,
+            author,
+            results,
+        })
+    }
+}
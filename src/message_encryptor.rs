@@ -0,0 +1,138 @@
+use js_sys::Array;
+use serde::Serialize;
+use tsify_next::Tsify;
+use wacore_libsignal::core::{DeviceId, ProtocolAddress as CoreProtocolAddress};
+use wacore_libsignal::protocol::{CiphertextMessageType, SignalProtocolError};
+use wasm_bindgen::prelude::*;
+
+use crate::jid::Jid;
+use crate::node_builder::BinaryNodeBuilder;
+use crate::padding::pad_random_max16;
+use crate::protocol_address::ProtocolAddress;
+use crate::session_cipher::encrypt_message;
+use crate::storage_adapter::{JsStorageAdapter, SignalStorage};
+
+#[wasm_bindgen]
+extern "C" {
+    /// `ProtocolAddress` instances, or their `"name.device"` string form as
+    /// returned by `toString()`.
+    #[wasm_bindgen(extends = Array, typescript_type = "(ProtocolAddress | string)[]")]
+    pub type AddressList;
+}
+
+/// A device that could not be encrypted for. Its session is left as it was.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct DeviceEncryptError {
+    pub jid: String,
+    pub error: String,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct EncryptForDevicesResult {
+    #[tsify(type = "BinaryNodeBuilder")]
+    #[serde(with = "serde_wasm_bindgen::preserve")]
+    pub participants: JsValue,
+    /// Device JIDs without a session.
+    pub missing_sessions: Vec<String>,
+    pub failures: Vec<DeviceEncryptError>,
+    pub should_include_device_identity: bool,
+}
+
+/// A `ProtocolAddress`, or a `"name.device"` string.
+fn parse_address(value: &JsValue) -> Option<CoreProtocolAddress> {
+    if let Some(address) = ProtocolAddress::from_js(value) {
+        return Some(address.0.clone());
+    }
+    let encoded = value.as_string()?;
+    let (name, device) = encoded.rsplit_once('.')?;
+    let device = device.parse::<u32>().ok()?;
+    Some(CoreProtocolAddress::new(
+        name.to_string(),
+        DeviceId::from(device),
+    ))
+}
+
+/// Fans an outgoing message out to every device of its recipients.
+#[wasm_bindgen(js_name = MessageEncryptor)]
+pub struct MessageEncryptor;
+
+#[wasm_bindgen(js_class = MessageEncryptor)]
+impl MessageEncryptor {
+    /// Pads `plaintext` once and encrypts it for each address, returning the
+    /// `<participants>` node with one `<to jid><enc/></to>` per device.
+    /// Devices without a session are skipped and listed as device JIDs in
+    /// `missingSessions`, devices that fail to encrypt are listed in
+    /// `failures` without failing the others; `shouldIncludeDeviceIdentity`
+    /// is set if any `pkmsg` was produced.
+    #[wasm_bindgen(js_name = encryptForDevices)]
+    pub async fn encrypt_for_devices(
+        plaintext: Vec<u8>,
+        addresses: AddressList,
+        storage: SignalStorage,
+    ) -> Result<EncryptForDevicesResult, JsValue> {
+        // Parsed up front so malformed input throws before any session ratchets.
+        let addresses = addresses
+            .iter()
+            .enumerate()
+            .map(|(i, value)| {
+                parse_address(&value).ok_or_else(|| {
+                    JsValue::from_str(&format!("addresses[{i}]: invalid protocol address"))
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let storage_adapter = JsStorageAdapter::new(storage);
+        let padded = pad_random_max16(&plaintext);
+
+        let mut participants = BinaryNodeBuilder::new("participants".to_string())?;
+        let mut missing_sessions = Vec::new();
+        let mut failures = Vec::new();
+        let mut include_device_identity = false;
+
+        for address in &addresses {
+            let jid = Jid::from_signal_address(address.name(), address.device_id().into());
+
+            let ciphertext = match encrypt_message(&storage_adapter, address, &padded).await {
+                Ok(ciphertext) => ciphertext,
+                Err(SignalProtocolError::SessionNotFound(_)) => {
+                    missing_sessions.push(jid.to_string());
+                    continue;
+                }
+                Err(e) => {
+                    failures.push(DeviceEncryptError {
+                        jid: jid.to_string(),
+                        error: format!("{:?}", e),
+                    });
+                    continue;
+                }
+            };
+            let enc_type = match ciphertext.message_type() {
+                CiphertextMessageType::PreKey => {
+                    include_device_identity = true;
+                    "pkmsg"
+                }
+                _ => "msg",
+            };
+
+            let mut enc = BinaryNodeBuilder::new("enc".to_string())?;
+            enc.set_attr("v".to_string(), "2".to_string());
+            enc.set_attr("type".to_string(), enc_type.to_string());
+            enc.set_bytes(ciphertext.serialize().to_vec());
+
+            let mut to = BinaryNodeBuilder::new("to".to_string())?;
+            to.set_attr("jid".to_string(), jid.to_string());
+            to.append_child(&enc)?;
+            participants.append_child(&to)?;
+        }
+
+        Ok(EncryptForDevicesResult {
+            participants: participants.into(),
+            missing_sessions,
+            failures,
+            should_include_device_identity: include_device_identity,
+        })
+    }
+}
@@ -0,0 +1,167 @@
+use js_sys::{Object, Uint8Array};
+use std::borrow::Cow;
+use std::rc::Rc;
+use wacore_binary::marshal::marshal_ref;
+use wacore_binary::node::{AttrsRef, NodeContentRef, NodeRef, NodeStr, ValueRef};
+use wasm_bindgen::convert::RefFromWasmAbi;
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{EncodingNode, js_to_node_ref};
+use crate::exported::{exported_ref, prototype_of};
+
+enum BuilderContent {
+    Empty,
+    Bytes(Vec<u8>),
+    Text(String),
+    Children(Vec<NodeRef<'static>>),
+}
+
+/// A node assembled on the Rust side. It can be passed to `encodeNode`,
+/// `encodeNodes` and `NoiseSession.encodeFrame` like any `BinaryNode`, but is
+/// marshalled directly instead of being read back through JS objects.
+#[wasm_bindgen]
+pub struct BinaryNodeBuilder {
+    tag: String,
+    attrs: Vec<(String, String)>,
+    content: BuilderContent,
+    /// Buffers of decoded nodes appended as children, which borrow from them.
+    buffers: Vec<Rc<[u8]>>,
+}
+
+thread_local! {
+    static BUILDER_PROTOTYPE: Object = prototype_of(BinaryNodeBuilder::empty(String::new()).into());
+}
+
+impl BinaryNodeBuilder {
+    fn empty(tag: String) -> BinaryNodeBuilder {
+        BinaryNodeBuilder {
+            tag,
+            attrs: Vec::new(),
+            content: BuilderContent::Empty,
+            buffers: Vec::new(),
+        }
+    }
+
+    /// Borrows the builder behind `value` if it is a `BinaryNodeBuilder`.
+    pub(crate) fn from_js(value: &JsValue) -> Option<<Self as RefFromWasmAbi>::Anchor> {
+        BUILDER_PROTOTYPE.with(|prototype| exported_ref::<Self>(value, prototype))
+    }
+
+    pub(crate) fn buffers(&self) -> &[Rc<[u8]>] {
+        &self.buffers
+    }
+
+    pub(crate) fn to_node_ref(&self) -> NodeRef<'static> {
+        let attrs = self
+            .attrs
+            .iter()
+            .map(|(k, v)| {
+                (
+                    NodeStr::Owned(k.clone().into()),
+                    ValueRef::String(NodeStr::Owned(v.clone().into())),
+                )
+            })
+            .collect();
+
+        let content = match &self.content {
+            BuilderContent::Empty => None,
+            BuilderContent::Bytes(bytes) => Some(NodeContentRef::Bytes(Cow::Owned(bytes.clone()))),
+            BuilderContent::Text(text) => {
+                Some(NodeContentRef::String(NodeStr::Owned(text.clone().into())))
+            }
+            BuilderContent::Children(children) => {
+                Some(NodeContentRef::Nodes(children.clone().into_boxed_slice()))
+            }
+        };
+
+        NodeRef::new(
+            NodeStr::Owned(self.tag.clone().into()),
+            AttrsRef::from_vec(attrs),
+            content,
+        )
+    }
+
+    fn push_child(&mut self, child: NodeRef<'static>) -> Result<(), JsValue> {
+        match &mut self.content {
+            BuilderContent::Children(children) => children.push(child),
+            BuilderContent::Empty => self.content = BuilderContent::Children(vec![child]),
+            _ => {
+                return Err(JsValue::from_str(&format!(
+                    "<{}> already has bytes or string content",
+                    self.tag
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    fn marshal(&self) -> Result<Vec<u8>, JsValue> {
+        marshal_ref(&self.to_node_ref()).map_err(|e| JsValue::from_str(&e.to_string()))
+    }
+}
+
+#[wasm_bindgen]
+impl BinaryNodeBuilder {
+    #[wasm_bindgen(constructor)]
+    pub fn new(tag: String) -> Result<BinaryNodeBuilder, JsValue> {
+        if tag.is_empty() {
+            return Err(JsValue::from_str("tag must be a non-empty string"));
+        }
+        Ok(BinaryNodeBuilder::empty(tag))
+    }
+
+    #[wasm_bindgen(getter)]
+    pub fn tag(&self) -> String {
+        self.tag.clone()
+    }
+
+    /// Sets or replaces an attribute, keeping insertion order.
+    #[wasm_bindgen(js_name = setAttr)]
+    pub fn set_attr(&mut self, key: String, value: String) {
+        match self.attrs.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => *existing = value,
+            None => self.attrs.push((key, value)),
+        }
+    }
+
+    #[wasm_bindgen(js_name = removeAttr)]
+    pub fn remove_attr(&mut self, key: &str) -> bool {
+        let before = self.attrs.len();
+        self.attrs.retain(|(k, _)| k != key);
+        self.attrs.len() != before
+    }
+
+    /// Appends a copy of another builder as a child.
+    #[wasm_bindgen(js_name = appendChild)]
+    pub fn append_child(&mut self, child: &BinaryNodeBuilder) -> Result<(), JsValue> {
+        self.push_child(child.to_node_ref())?;
+        self.buffers.extend(child.buffers.iter().cloned());
+        Ok(())
+    }
+
+    /// Appends a `BinaryNode` object or decoded `InternalBinaryNode` as a
+    /// child. Decoded nodes are shared, not copied.
+    #[wasm_bindgen(js_name = appendNode)]
+    pub fn append_node(&mut self, node: EncodingNode) -> Result<(), JsValue> {
+        let (node, buffers) = js_to_node_ref(&node)?.into_parts();
+        self.push_child(node)?;
+        self.buffers.extend(buffers);
+        Ok(())
+    }
+
+    /// Replaces the content with raw bytes.
+    #[wasm_bindgen(js_name = setBytes)]
+    pub fn set_bytes(&mut self, bytes: Vec<u8>) {
+        self.content = BuilderContent::Bytes(bytes);
+    }
+
+    /// Replaces the content with a string.
+    #[wasm_bindgen(js_name = setString)]
+    pub fn set_string(&mut self, text: String) {
+        self.content = BuilderContent::Text(text);
+    }
+
+    pub fn encode(&self) -> Result<Uint8Array, JsValue> {
+        Ok(Uint8Array::from(self.marshal()?.as_slice()))
+    }
+}
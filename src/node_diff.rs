@@ -0,0 +1,205 @@
+use js_sys::Uint8Array;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tsify_next::Tsify;
+use wacore_binary::node::{NodeContentRef, NodeRef};
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{EncodingNode, JsNodeRef, js_to_node_ref, unmarshal_owned};
+use crate::decode_limits::DecodeLimits;
+use crate::node_xml::push_hex;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "Uint8Array | EncodingNode | BinaryNodeBuilder")]
+    pub type DiffInput;
+}
+
+/// One difference between two nodes, e.g. `{ path: "iq.attrs.to", kind: "attr", ... }`.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct NodeDifference {
+    pub path: String,
+    /// `tag`, `attr`, `content` or `children`
+    pub kind: String,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub left: Option<String>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub right: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct BinaryNodeDiff {
+    pub equal: bool,
+    pub differences: Vec<NodeDifference>,
+}
+
+fn parse_input(input: &DiffInput) -> Result<JsNodeRef<'static>, JsValue> {
+    if let Some(bytes) = input.dyn_ref::<Uint8Array>() {
+        let (owned_data, node) = unmarshal_owned(&bytes.to_vec(), &DecodeLimits::default())?;
+        return Ok(JsNodeRef::owned(node, vec![owned_data]));
+    }
+    js_to_node_ref(input.unchecked_ref::<EncodingNode>())
+}
+
+/// Content with strings and bytes folded together, and no content treated
+/// like an empty child list.
+enum Normalized<'a> {
+    Bytes(&'a [u8]),
+    Nodes(&'a [NodeRef<'static>]),
+}
+
+fn normalize<'a>(node: &'a NodeRef<'static>) -> Normalized<'a> {
+    match node.content.as_deref() {
+        None => Normalized::Nodes(&[]),
+        Some(NodeContentRef::Nodes(nodes)) => Normalized::Nodes(nodes),
+        Some(NodeContentRef::Bytes(bytes)) => Normalized::Bytes(bytes),
+        Some(NodeContentRef::String(s)) => Normalized::Bytes(s.as_bytes()),
+    }
+}
+
+fn describe_bytes(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) if !text.chars().any(char::is_control) => text.to_string(),
+        _ => {
+            let mut out = String::from("hex:");
+            push_hex(&mut out, bytes);
+            out
+        }
+    }
+}
+
+struct Differ {
+    differences: Vec<NodeDifference>,
+    limit: usize,
+}
+
+impl Differ {
+    fn push(&mut self, path: String, kind: &str, left: Option<String>, right: Option<String>) {
+        self.differences.push(NodeDifference {
+            path,
+            kind: kind.to_string(),
+            left,
+            right,
+        });
+    }
+
+    fn full(&self) -> bool {
+        self.differences.len() >= self.limit
+    }
+
+    fn compare(&mut self, a: &NodeRef<'static>, b: &NodeRef<'static>, path: &str) {
+        if *a.tag != *b.tag {
+            self.push(
+                format!("{path}.tag"),
+                "tag",
+                Some(a.tag.to_string()),
+                Some(b.tag.to_string()),
+            );
+            return;
+        }
+
+        let left: BTreeMap<&str, String> = a
+            .attrs
+            .as_slice()
+            .iter()
+            .map(|(k, v)| (&**k, v.as_str().to_string()))
+            .collect();
+        let right: BTreeMap<&str, String> = b
+            .attrs
+            .as_slice()
+            .iter()
+            .map(|(k, v)| (&**k, v.as_str().to_string()))
+            .collect();
+        for key in left
+            .keys()
+            .chain(right.keys().filter(|k| !left.contains_key(*k)))
+        {
+            if self.full() {
+                return;
+            }
+            let (l, r) = (left.get(key), right.get(key));
+            if l != r {
+                self.push(
+                    format!("{path}.attrs.{key}"),
+                    "attr",
+                    l.cloned(),
+                    r.cloned(),
+                );
+            }
+        }
+
+        match (normalize(a), normalize(b)) {
+            (Normalized::Bytes(l), Normalized::Bytes(r)) => {
+                if l != r {
+                    self.push(
+                        format!("{path}.content"),
+                        "content",
+                        Some(describe_bytes(l)),
+                        Some(describe_bytes(r)),
+                    );
+                }
+            }
+            (Normalized::Nodes(l), Normalized::Nodes(r)) => {
+                if l.len() != r.len() {
+                    self.push(
+                        format!("{path}.content"),
+                        "children",
+                        Some(l.len().to_string()),
+                        Some(r.len().to_string()),
+                    );
+                }
+                for (i, (l, r)) in l.iter().zip(r.iter()).enumerate() {
+                    if self.full() {
+                        return;
+                    }
+                    self.compare(l, r, &format!("{path}.content[{i}]"));
+                }
+            }
+            (Normalized::Bytes(l), Normalized::Nodes(r)) => self.push(
+                format!("{path}.content"),
+                "content",
+                Some(describe_bytes(l)),
+                Some(format!("{} children", r.len())),
+            ),
+            (Normalized::Nodes(l), Normalized::Bytes(r)) => self.push(
+                format!("{path}.content"),
+                "content",
+                Some(format!("{} children", l.len())),
+                Some(describe_bytes(r)),
+            ),
+        }
+    }
+}
+
+fn diff(a: &DiffInput, b: &DiffInput, limit: usize) -> Result<Vec<NodeDifference>, JsValue> {
+    let a = parse_input(a)?;
+    let b = parse_input(b)?;
+    let mut differ = Differ {
+        differences: Vec::new(),
+        limit,
+    };
+    differ.compare(&a, &b, &a.tag);
+    Ok(differ.differences)
+}
+
+/// Compares two nodes semantically: attribute order is ignored and string
+/// content equals the same bytes. Accepts encoded bytes or node objects.
+#[wasm_bindgen(js_name = binaryNodeEquals)]
+pub fn binary_node_equals(a: DiffInput, b: DiffInput) -> Result<bool, JsValue> {
+    Ok(diff(&a, &b, 1)?.is_empty())
+}
+
+/// Lists every difference between two nodes, addressed like `iq.content[0].attrs.to`.
+#[wasm_bindgen(js_name = binaryNodeDiff)]
+pub fn binary_node_diff(a: DiffInput, b: DiffInput) -> Result<BinaryNodeDiff, JsValue> {
+    let differences = diff(&a, &b, usize::MAX)?;
+    Ok(BinaryNodeDiff {
+        equal: differences.is_empty(),
+        differences,
+    })
+}
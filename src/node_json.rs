@@ -0,0 +1,90 @@
+use base64::prelude::*;
+use js_sys::Object;
+use wacore_binary::marshal::marshal_ref;
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{InternalBinaryNode, decode_node, node_ref_to_js_with, portable_to_node_ref};
+
+#[wasm_bindgen(typescript_custom_section)]
+const TS_PORTABLE_NODE: &str = r#"
+/** Byte content in a `PortableBinaryNode`. */
+export interface PortableBytes {
+    type: "bytes";
+    base64: string;
+}
+
+/**
+ * A `BinaryNode` that survives `JSON.stringify`: byte content is tagged
+ * base64, string content stays a plain string.
+ */
+export interface PortableBinaryNode {
+    tag: string;
+    attrs: { [key: string]: string };
+    content?: PortableBinaryNode[] | string | PortableBytes;
+}
+"#;
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(typescript_type = "PortableBinaryNode")]
+    pub type PortableBinaryNode;
+
+    #[wasm_bindgen(typescript_type = "PortableBinaryNode | string")]
+    pub type PortableInput;
+}
+
+const BYTES_TYPE: &str = "bytes";
+
+fn set(obj: &Object, key: &str, value: &JsValue) {
+    let _ = js_sys::Reflect::set(obj, &JsValue::from_str(key), value);
+}
+
+fn get(obj: &JsValue, key: &str) -> JsValue {
+    js_sys::Reflect::get(obj, &JsValue::from_str(key)).unwrap_or(JsValue::UNDEFINED)
+}
+
+fn portable_content(bytes: &[u8]) -> JsValue {
+    let tagged = Object::new();
+    set(&tagged, "type", &JsValue::from_str(BYTES_TYPE));
+    set(
+        &tagged,
+        "base64",
+        &JsValue::from_str(&BASE64_STANDARD.encode(bytes)),
+    );
+    tagged.into()
+}
+
+/// The bytes of `{ type: "bytes", base64 }` content, or `None` for other content.
+pub(crate) fn portable_bytes(content: &JsValue) -> Option<Result<Vec<u8>, &'static str>> {
+    if get(content, "type").as_string().as_deref() != Some(BYTES_TYPE) {
+        return None;
+    }
+    Some(
+        get(content, "base64")
+            .as_string()
+            .and_then(|b64| BASE64_STANDARD.decode(b64).ok())
+            .ok_or("expected a base64 string"),
+    )
+}
+
+#[wasm_bindgen]
+impl InternalBinaryNode {
+    /// The node as JSON-safe data, with byte content as tagged base64.
+    #[wasm_bindgen(js_name = toPortableJSON)]
+    pub fn to_portable_json(&self) -> Result<PortableBinaryNode, JsValue> {
+        let node = self.current_node_ref()?;
+        Ok(node_ref_to_js_with(&node, &portable_content).unchecked_into())
+    }
+
+    /// Rebuilds a node from `toPortableJSON()` output or its `JSON.stringify`d form.
+    #[wasm_bindgen(js_name = fromPortableJSON)]
+    pub fn from_portable_json(json: PortableInput) -> Result<InternalBinaryNode, JsValue> {
+        let value = match json.as_string() {
+            Some(text) => js_sys::JSON::parse(&text)?,
+            None => json.into(),
+        };
+        let node = portable_to_node_ref(&value)?;
+        let bytes = marshal_ref(&node).map_err(|e| JsValue::from_str(&e.to_string()))?;
+        decode_node(bytes, None)
+    }
+}
@@ -0,0 +1,375 @@
+use base64::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::fmt::Write as _;
+use tsify_next::Tsify;
+use wacore_binary::node::{AttrsRef, NodeContentRef, NodeRef, NodeStr, ValueRef};
+use wasm_bindgen::prelude::*;
+
+use crate::binary::{InternalBinaryNode, node_ref_to_js, unmarshal_owned};
+use crate::decode_limits::DecodeLimits;
+
+const HEX_PREFIX: &str = "hex:";
+const BASE64_PREFIX: &str = "base64:";
+/// Content of a node with an empty child list, as opposed to `<a/>` (none).
+const EMPTY_NODES: &str = "[]";
+/// Nesting accepted by `parseXmlNode`, which parses recursively.
+const MAX_PARSE_DEPTH: usize = 128;
+
+/// How byte content is rendered by `toXML`/`binaryNodeToString`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "lowercase")]
+pub enum XmlBytesFormat {
+    /// `hex:0a0b...`, can be parsed back
+    #[default]
+    Hex,
+    /// `base64:CgsM...`, can be parsed back
+    Base64,
+    /// `[12 bytes]`, for logs only
+    Length,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Tsify)]
+#[tsify(from_wasm_abi)]
+pub struct XmlOptions {
+    /// Spaces per nesting level, default 2. `0` renders everything on one line.
+    #[tsify(optional)]
+    pub indent: Option<u32>,
+    #[tsify(optional)]
+    pub bytes: Option<XmlBytesFormat>,
+}
+
+fn escape_into(out: &mut String, text: &str) {
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            _ => out.push(c),
+        }
+    }
+}
+
+/// Text that would be read back as something other than a string.
+fn is_marker(text: &str) -> bool {
+    let trimmed = text.trim();
+    trimmed.starts_with(HEX_PREFIX)
+        || trimmed.starts_with(BASE64_PREFIX)
+        || trimmed == EMPTY_NODES
+        || (trimmed.starts_with('[') && trimmed.ends_with(" bytes]"))
+}
+
+/// Writes string content, escaping the first character of text that looks
+/// like a byte or empty-list marker so it parses back as a string.
+fn write_text(out: &mut String, text: &str) {
+    if !is_marker(text) {
+        escape_into(out, text);
+        return;
+    }
+    let trimmed = text.trim_start();
+    escape_into(out, &text[..text.len() - trimmed.len()]);
+    let mut chars = trimmed.chars();
+    if let Some(first) = chars.next() {
+        let _ = write!(out, "&#{};", first as u32);
+    }
+    escape_into(out, chars.as_str());
+}
+
+/// Appends `bytes` as lowercase hex.
+pub(crate) fn push_hex(out: &mut String, bytes: &[u8]) {
+    for b in bytes {
+        let _ = write!(out, "{b:02x}");
+    }
+}
+
+fn write_bytes(out: &mut String, bytes: &[u8], format: XmlBytesFormat) {
+    match format {
+        XmlBytesFormat::Hex => {
+            out.push_str(HEX_PREFIX);
+            push_hex(out, bytes);
+        }
+        XmlBytesFormat::Base64 => {
+            out.push_str(BASE64_PREFIX);
+            out.push_str(&BASE64_STANDARD.encode(bytes));
+        }
+        XmlBytesFormat::Length => {
+            let _ = write!(out, "[{} bytes]", bytes.len());
+        }
+    }
+}
+
+fn write_node(out: &mut String, node: &NodeRef<'_>, options: &XmlOptions, depth: usize) {
+    let indent = options.indent.unwrap_or(2) as usize;
+    let pad = " ".repeat(indent * depth);
+
+    out.push_str(&pad);
+    out.push('<');
+    out.push_str(&node.tag);
+    for (key, value) in node.attrs.as_slice().iter() {
+        let _ = write!(out, " {}=\"", &**key);
+        escape_into(out, &value.as_str());
+        out.push('"');
+    }
+
+    match node.content.as_deref() {
+        None => out.push_str("/>"),
+        Some(NodeContentRef::Nodes(nodes)) if nodes.is_empty() => {
+            let _ = write!(out, ">{EMPTY_NODES}</{}>", &*node.tag);
+        }
+        Some(NodeContentRef::Nodes(nodes)) => {
+            out.push('>');
+            for child in nodes.iter() {
+                if indent > 0 {
+                    out.push('\n');
+                }
+                write_node(out, child, options, depth + 1);
+            }
+            if indent > 0 {
+                out.push('\n');
+                out.push_str(&pad);
+            }
+            let _ = write!(out, "</{}>", &*node.tag);
+        }
+        Some(NodeContentRef::String(text)) => {
+            out.push('>');
+            write_text(out, text);
+            let _ = write!(out, "</{}>", &*node.tag);
+        }
+        Some(NodeContentRef::Bytes(bytes)) => {
+            out.push('>');
+            write_bytes(out, bytes, options.bytes.unwrap_or_default());
+            let _ = write!(out, "</{}>", &*node.tag);
+        }
+    }
+}
+
+pub(crate) fn node_to_xml(node: &NodeRef<'_>, options: &XmlOptions) -> String {
+    let mut out = String::new();
+    write_node(&mut out, node, options, 0);
+    out
+}
+
+#[wasm_bindgen]
+impl InternalBinaryNode {
+    /// Renders the node as indented XML, e.g. `<iq id="1"><ping/></iq>`.
+    #[wasm_bindgen(js_name = toXML)]
+    pub fn to_xml(&self, options: Option<XmlOptions>) -> Result<String, JsValue> {
+        let node = self.current_node_ref()?;
+        Ok(node_to_xml(&node, &options.unwrap_or_default()))
+    }
+}
+
+/// Decodes a binary frame and renders it as XML.
+#[wasm_bindgen(js_name = binaryNodeToString)]
+pub fn binary_node_to_string(data: &[u8], options: Option<XmlOptions>) -> Result<String, JsValue> {
+    let (_owned, node) = unmarshal_owned(data, &DecodeLimits::default())?;
+    Ok(node_to_xml(&node, &options.unwrap_or_default()))
+}
+
+struct XmlParser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> XmlParser<'a> {
+    fn error(&self, message: &str) -> JsValue {
+        JsValue::from_str(&format!(
+            "Invalid XML node at offset {}: {message}",
+            self.pos
+        ))
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let trimmed = self.rest().trim_start();
+        self.pos = self.input.len() - trimmed.len();
+    }
+
+    fn expect(&mut self, token: &str) -> Result<(), JsValue> {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{token}`")))
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, JsValue> {
+        let rest = self.rest();
+        let end = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=' | '<'))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(self.error("expected a name"));
+        }
+        self.pos += end;
+        Ok(&rest[..end])
+    }
+
+    fn quoted(&mut self) -> Result<String, JsValue> {
+        let quote = match self.rest().chars().next() {
+            Some(q @ ('"' | '\'')) => q,
+            _ => return Err(self.error("expected a quoted attribute value")),
+        };
+        self.pos += 1;
+        let rest = self.rest();
+        let end = rest
+            .find(quote)
+            .ok_or_else(|| self.error("unterminated attribute value"))?;
+        self.pos += end + 1;
+        unescape(&rest[..end]).map_err(|e| self.error(&e))
+    }
+
+    fn node(&mut self, depth: usize) -> Result<NodeRef<'static>, JsValue> {
+        if depth > MAX_PARSE_DEPTH {
+            return Err(self.error(&format!("nested more than {MAX_PARSE_DEPTH} levels deep")));
+        }
+        self.skip_whitespace();
+        self.expect("<")?;
+        let tag = self.name()?;
+
+        let mut attrs = Vec::new();
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("/>") {
+                self.pos += 2;
+                return Ok(NodeRef::new(
+                    NodeStr::Owned(tag.into()),
+                    AttrsRef::from_vec(attrs),
+                    None,
+                ));
+            }
+            if self.rest().starts_with('>') {
+                self.pos += 1;
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let value = self.quoted()?;
+            attrs.push((
+                NodeStr::Owned(key.into()),
+                ValueRef::String(NodeStr::Owned(value.into())),
+            ));
+        }
+
+        let text_start = self.pos;
+        self.skip_whitespace();
+        let content = if self.rest().starts_with("</") {
+            let text = &self.input[text_start..self.pos];
+            Some(NodeContentRef::String(NodeStr::Owned(text.into())))
+        } else if self.rest().starts_with('<') {
+            let mut children = Vec::new();
+            while !self.rest().starts_with("</") {
+                children.push(self.node(depth + 1)?);
+                self.skip_whitespace();
+            }
+            Some(NodeContentRef::Nodes(children.into_boxed_slice()))
+        } else {
+            self.pos = text_start;
+            let end = self
+                .rest()
+                .find('<')
+                .ok_or_else(|| self.error(&format!("missing `</{tag}>`")))?;
+            let text = &self.rest()[..end];
+            self.pos += end;
+            Some(parse_text_content(text).map_err(|e| self.error(&e))?)
+        };
+
+        self.expect("</")?;
+        if self.name()? != tag {
+            return Err(self.error(&format!("expected `</{tag}>`")));
+        }
+        self.skip_whitespace();
+        self.expect(">")?;
+
+        Ok(NodeRef::new(
+            NodeStr::Owned(tag.into()),
+            AttrsRef::from_vec(attrs),
+            content,
+        ))
+    }
+}
+
+fn unescape(text: &str) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let semi = rest.find(';').ok_or("unterminated entity")?;
+        let entity = &rest[..=semi];
+        out.push(match entity {
+            "&amp;" => '&',
+            "&lt;" => '<',
+            "&gt;" => '>',
+            "&quot;" => '"',
+            "&apos;" => '\'',
+            _ => char_reference(entity).ok_or_else(|| format!("unknown entity `{entity}`"))?,
+        });
+        rest = &rest[semi + 1..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// `&#65;` or `&#x41;`.
+fn char_reference(entity: &str) -> Option<char> {
+    let code = entity.strip_prefix("&#")?.strip_suffix(';')?;
+    let code = match code.strip_prefix('x') {
+        Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+        None => code.parse().ok()?,
+    };
+    char::from_u32(code)
+}
+
+fn parse_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+fn parse_text_content(text: &str) -> Result<NodeContentRef<'static>, String> {
+    let trimmed = text.trim();
+    if let Some(hex) = trimmed.strip_prefix(HEX_PREFIX) {
+        let bytes = parse_hex(hex).ok_or("invalid hex content")?;
+        return Ok(NodeContentRef::Bytes(Cow::Owned(bytes)));
+    }
+    if let Some(b64) = trimmed.strip_prefix(BASE64_PREFIX) {
+        let bytes = BASE64_STANDARD
+            .decode(b64)
+            .map_err(|e| format!("invalid base64 content: {e}"))?;
+        return Ok(NodeContentRef::Bytes(Cow::Owned(bytes)));
+    }
+    if trimmed == EMPTY_NODES {
+        return Ok(NodeContentRef::Nodes(Box::default()));
+    }
+    if trimmed.starts_with('[') && trimmed.ends_with(" bytes]") {
+        return Err("byte content was elided and cannot be parsed".to_string());
+    }
+    Ok(NodeContentRef::String(NodeStr::Owned(
+        unescape(text)?.into(),
+    )))
+}
+
+/// Parses the output of `toXML` back into a `BinaryNode` that `encodeNode` accepts.
+#[wasm_bindgen(js_name = parseXmlNode)]
+pub fn parse_xml_node(xml: &str) -> Result<crate::binary::BinaryNodeObject, JsValue> {
+    let mut parser = XmlParser { input: xml, pos: 0 };
+    let node = parser.node(1)?;
+    parser.skip_whitespace();
+    if !parser.rest().is_empty() {
+        return Err(parser.error("unexpected trailing content"));
+    }
+    Ok(node_ref_to_js(&node).unchecked_into())
+}
@@ -0,0 +1,232 @@
+use prost::Message as _;
+use serde::{Deserialize, Serialize};
+use tsify_next::Tsify;
+use waproto::whatsapp::CertChain;
+use waproto::whatsapp::cert_chain::NoiseCertificate;
+use waproto::whatsapp::cert_chain::noise_certificate::Details;
+use wasm_bindgen::prelude::*;
+
+use crate::curve::verify_signature;
+
+/// Public key of WhatsApp's noise certificate root (Baileys' `WA_CERT_DETAILS`).
+pub const WA_CERT_ROOT_PUBLIC_KEY: [u8; 32] = [
+    0x14, 0x23, 0x75, 0x57, 0x4d, 0x0a, 0x58, 0x71, 0x66, 0xaa, 0xe7, 0x1e, 0xbe, 0x51, 0x64, 0x37,
+    0xc4, 0xa2, 0x8b, 0x73, 0xe3, 0x69, 0x5c, 0x6c, 0xe1, 0xf7, 0xf9, 0x54, 0x5d, 0xa8, 0xee, 0x6b,
+];
+pub const WA_CERT_ROOT_SERIAL: u32 = 0;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateOptions {
+    /// Defaults to WhatsApp's root key; override for a mock server.
+    #[tsify(optional, type = "Uint8Array")]
+    #[serde(default, with = "serde_bytes")]
+    pub root_public_key: Option<Vec<u8>>,
+    /// Serial the intermediate must be issued by, default `0`.
+    #[tsify(optional)]
+    pub root_serial: Option<u32>,
+    /// Unix seconds to check validity periods against, default now.
+    #[tsify(optional)]
+    pub now: Option<u64>,
+}
+
+/// How `completeHandshake` checks the server certificate: the same fields as
+/// `CertificateOptions`, plus an explicit opt-out.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct HandshakeCertificateOptions {
+    /// Sends `ClientFinish` without checking the chain, e.g. against a mock
+    /// server that has no certificate from a known root.
+    #[tsify(optional)]
+    pub skip_verification: Option<bool>,
+    #[tsify(optional, type = "Uint8Array")]
+    #[serde(default, with = "serde_bytes")]
+    pub root_public_key: Option<Vec<u8>>,
+    #[tsify(optional)]
+    pub root_serial: Option<u32>,
+    #[tsify(optional)]
+    pub now: Option<u64>,
+}
+
+impl From<HandshakeCertificateOptions> for CertificateOptions {
+    fn from(options: HandshakeCertificateOptions) -> Self {
+        Self {
+            root_public_key: options.root_public_key,
+            root_serial: options.root_serial,
+            now: options.now,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub enum CertificateError {
+    /// The payload is not a `CertChain` with both certificates.
+    Malformed,
+    IntermediateSignature,
+    IntermediateIssuer,
+    LeafSignature,
+    LeafIssuer,
+    Expired,
+    NotYetValid,
+    /// The leaf key is not the static key the server used in the handshake.
+    StaticKeyMismatch,
+}
+
+impl CertificateError {
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            CertificateError::Malformed => "malformed",
+            CertificateError::IntermediateSignature => "intermediateSignature",
+            CertificateError::IntermediateIssuer => "intermediateIssuer",
+            CertificateError::LeafSignature => "leafSignature",
+            CertificateError::LeafIssuer => "leafIssuer",
+            CertificateError::Expired => "expired",
+            CertificateError::NotYetValid => "notYetValid",
+            CertificateError::StaticKeyMismatch => "staticKeyMismatch",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateDetails {
+    pub serial: u32,
+    pub issuer_serial: u32,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub key: Vec<u8>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_before: Option<u64>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub not_after: Option<u64>,
+}
+
+impl From<Details> for CertificateDetails {
+    fn from(details: Details) -> Self {
+        Self {
+            serial: details.serial.unwrap_or_default(),
+            issuer_serial: details.issuer_serial.unwrap_or_default(),
+            key: details.key.unwrap_or_default(),
+            not_before: details.not_before,
+            not_after: details.not_after,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateVerification {
+    pub valid: bool,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<CertificateError>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub intermediate: Option<CertificateDetails>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub leaf: Option<CertificateDetails>,
+}
+
+fn signed_details(cert: NoiseCertificate, signer: &[u8]) -> Option<(Details, bool)> {
+    let details_bytes = cert.details?;
+    let signature = cert.signature?;
+    let details = Details::decode(details_bytes.as_slice()).ok()?;
+    let signed = verify_signature(signer, &details_bytes, &signature).unwrap_or(false);
+    Some((details, signed))
+}
+
+fn check_validity(details: &CertificateDetails, now: u64) -> Option<CertificateError> {
+    if details.not_before.is_some_and(|t| now < t) {
+        return Some(CertificateError::NotYetValid);
+    }
+    if details.not_after.is_some_and(|t| now > t) {
+        return Some(CertificateError::Expired);
+    }
+    None
+}
+
+/// Verifies `root -> intermediate -> leaf` and that the leaf certifies `server_static_key`.
+pub(crate) fn verify_chain(
+    certificate: &[u8],
+    server_static_key: &[u8],
+    options: &CertificateOptions,
+) -> CertificateVerification {
+    let mut result = CertificateVerification {
+        valid: false,
+        error: None,
+        intermediate: None,
+        leaf: None,
+    };
+    let fail = |mut result: CertificateVerification, error| {
+        result.error = Some(error);
+        result
+    };
+
+    let Some((intermediate, leaf)) = CertChain::decode(certificate)
+        .ok()
+        .and_then(|chain| Some((chain.intermediate?, chain.leaf?)))
+    else {
+        return fail(result, CertificateError::Malformed);
+    };
+
+    let root_key = options
+        .root_public_key
+        .as_deref()
+        .unwrap_or(&WA_CERT_ROOT_PUBLIC_KEY);
+    let Some((intermediate, intermediate_signed)) = signed_details(intermediate, root_key) else {
+        return fail(result, CertificateError::Malformed);
+    };
+    let intermediate = CertificateDetails::from(intermediate);
+    let Some((leaf, leaf_signed)) = signed_details(leaf, &intermediate.key) else {
+        result.intermediate = Some(intermediate);
+        return fail(result, CertificateError::Malformed);
+    };
+    let leaf = CertificateDetails::from(leaf);
+
+    let now = options
+        .now
+        .unwrap_or_else(|| (js_sys::Date::now() / 1000.0) as u64);
+    let error = if !intermediate_signed {
+        Some(CertificateError::IntermediateSignature)
+    } else if intermediate.issuer_serial != options.root_serial.unwrap_or(WA_CERT_ROOT_SERIAL) {
+        Some(CertificateError::IntermediateIssuer)
+    } else if !leaf_signed {
+        Some(CertificateError::LeafSignature)
+    } else if leaf.issuer_serial != intermediate.serial {
+        Some(CertificateError::LeafIssuer)
+    } else if let Some(error) =
+        check_validity(&intermediate, now).or_else(|| check_validity(&leaf, now))
+    {
+        Some(error)
+    } else if leaf.key != server_static_key {
+        Some(CertificateError::StaticKeyMismatch)
+    } else {
+        None
+    };
+
+    result.valid = error.is_none();
+    result.error = error;
+    result.intermediate = Some(intermediate);
+    result.leaf = Some(leaf);
+    result
+}
+
+/// Verifies a decrypted `ServerHello` certificate payload against the root
+/// key and the server static key from the same handshake.
+#[wasm_bindgen(js_name = verifyCertificateChain)]
+pub fn verify_certificate_chain(
+    certificate: &[u8],
+    server_static_key: &[u8],
+    options: Option<CertificateOptions>,
+) -> CertificateVerification {
+    verify_chain(certificate, server_static_key, &options.unwrap_or_default())
+}
@@ -1,57 +1,733 @@
-use js_sys::Uint8Array;
-use wacore_binary::consts::NOISE_PATTERN_XX as NOISE_MODE;
-use wacore_binary::marshal::marshal_ref;
-use wacore_noise::framing::{FrameDecoder, encode_frame_into};
-use wacore_noise::{NoiseCipher, NoiseHandshake, build_handshake_header};
+use js_sys::{Object, Promise, Reflect, Uint8Array};
+use prost::Message as _;
+use serde::Serialize;
+use std::cell::RefCell;
+use std::rc::Rc;
+use tsify_next::Tsify;
+use wacore_noise::framing::encode_frame_into;
+use wacore_noise::{NoiseCipher, build_handshake_header};
+use waproto::whatsapp::HandshakeMessage;
+use waproto::whatsapp::handshake_message::{ClientFinish, ClientHello};
 use wasm_bindgen::prelude::*;
+use wasm_bindgen_futures::{JsFuture, future_to_promise};
+use web_sys::{
+    ReadableStream, ReadableStreamDefaultController, ReadableStreamDefaultReader, TransformStream,
+    TransformStreamDefaultController,
+};
 
-use crate::binary::{EncodingNode, decode_node, js_to_node_ref};
+use crate::binary::{EncodeOptions, EncodingNode, decode_node, marshal_js_node};
+use crate::curve::{KeyPair, generate_key_pair};
+use crate::decode_limits::DecodeLimits;
+use crate::frame_limits::{FrameDecoder, FrameLimits, check_frame_len};
+use crate::js_error::error_message;
+use crate::noise_cert::{
+    CertificateOptions, CertificateVerification, HandshakeCertificateOptions, verify_chain,
+};
+use crate::noise_state::{Handshake, NoiseRole, NoiseState, TransportKeys};
+
+/// The responder's half of the handshake, to send back as `ServerHello`.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct ServerHelloParts {
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub ephemeral: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(rename = "static", with = "serde_bytes")]
+    pub static_key: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+}
+
+/// The initiator's static key and payload, decrypted from `ClientFinish`.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct ClientFinishParts {
+    #[tsify(type = "Uint8Array")]
+    #[serde(rename = "static", with = "serde_bytes")]
+    pub static_key: Vec<u8>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub payload: Vec<u8>,
+}
+
+/// Frames sent and received since `finishInit`.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct FrameCounters {
+    pub read: u32,
+    pub write: u32,
+    /// Frames left in the direction closest to exhaustion.
+    pub remaining: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "lowercase")]
+pub enum FrameStage {
+    Decrypt,
+    Unmarshal,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct FrameError {
+    /// Position of the failed frame in `nodes`, which holds `undefined` there.
+    pub index: u32,
+    pub stage: FrameStage,
+    pub message: String,
+}
+
+/// Result of `decodeFrameSettled`, in frame order.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+pub struct SettledFrames {
+    /// One entry per completed frame, `undefined` where it failed.
+    #[tsify(type = "(InternalBinaryNode | Uint8Array | undefined)[]")]
+    #[serde(with = "serde_wasm_bindgen::preserve")]
+    pub nodes: js_sys::Array,
+    pub errors: Vec<FrameError>,
+}
+
+const GCM_TAG_LEN: usize = 16;
+
+/// The transport nonce is a 32-bit frame counter and WhatsApp's Noise has no
+/// rekey, so a direction that reaches `u32::MAX` is closed for good.
+fn next_counter(counter: u32, direction: &str) -> Result<u32, JsValue> {
+    if counter == u32::MAX {
+        return Err(JsValue::from_str(&format!(
+            "Noise {direction} counter exhausted: close the connection and reconnect"
+        )));
+    }
+    Ok(counter + 1)
+}
+
+/// Noise DH takes bare 32-byte keys; `generateKeyPair` adds a `0x05` prefix.
+fn raw_public_key(key: &[u8]) -> &[u8] {
+    match key {
+        [0x05, rest @ ..] if rest.len() == 32 => rest,
+        _ => key,
+    }
+}
+
+/// `ED`, version `0, 1` and a 3-byte routing info length precede the noise
+/// header when the initiator has routing info.
+const EDGE_ROUTING_MAGIC: &[u8; 2] = b"ED";
+const EDGE_ROUTING_PREFIX_LEN: usize = 7;
+
+/// Collects the initiator's intro header on the responder side, which may
+/// arrive split across chunks.
+struct IntroReader {
+    noise_header: Vec<u8>,
+    buffered: Vec<u8>,
+}
+
+impl IntroReader {
+    /// Intro length as far as the buffered bytes tell.
+    fn expected_len(&self) -> usize {
+        match self.buffered.get(..2) {
+            None => 2,
+            Some(magic) if magic == EDGE_ROUTING_MAGIC => {
+                match self.buffered.get(4..EDGE_ROUTING_PREFIX_LEN) {
+                    Some(len) => {
+                        let routing_len = u32::from_be_bytes([0, len[0], len[1], len[2]]);
+                        EDGE_ROUTING_PREFIX_LEN + routing_len as usize + self.noise_header.len()
+                    }
+                    None => EDGE_ROUTING_PREFIX_LEN,
+                }
+            }
+            Some(_) => self.noise_header.len(),
+        }
+    }
+
+    /// Consumes intro bytes from `data`, returning the rest once the intro
+    /// is complete.
+    fn read<'a>(&mut self, mut data: &'a [u8]) -> Result<Option<&'a [u8]>, JsValue> {
+        loop {
+            let expected = self.expected_len();
+            let take = expected.saturating_sub(self.buffered.len()).min(data.len());
+            self.buffered.extend_from_slice(&data[..take]);
+            data = &data[take..];
+            if self.buffered.len() < expected {
+                return Ok(None);
+            }
+            if self.expected_len() == expected {
+                break;
+            }
+        }
+
+        let routed = self.buffered.starts_with(EDGE_ROUTING_MAGIC);
+        let bad_version = routed && self.buffered[2..4] != [0, 1];
+        if bad_version || !self.buffered.ends_with(&self.noise_header) {
+            return Err(JsValue::from_str(
+                "Unexpected intro header from the initiator",
+            ));
+        }
+        Ok(Some(data))
+    }
+}
+
+#[wasm_bindgen]
+extern "C" {
+    #[wasm_bindgen(extends = ReadableStream, typescript_type = "ReadableStream<InternalBinaryNode>")]
+    pub type NodeStream;
+
+    #[wasm_bindgen(
+        extends = TransformStream,
+        typescript_type = "TransformStream<EncodingNode | BinaryNodeBuilder, Uint8Array>"
+    )]
+    pub type FrameEncoderStream;
+}
 
 /// NoiseSession implements the Noise_XX_25519_AESGCM_SHA256 protocol pattern
 /// with combined binary encoding/decoding operations for reduced WASM boundary crossings.
+///
+/// There is no rekeying: when a transport counter is exhausted the session
+/// refuses to encrypt or decrypt further frames and must be replaced.
 #[wasm_bindgen]
 pub struct NoiseSession {
-    handshake: Option<NoiseHandshake>,
+    state: Rc<RefCell<SessionState>>,
+}
+
+impl From<SessionState> for NoiseSession {
+    fn from(state: SessionState) -> Self {
+        Self {
+            state: Rc::new(RefCell::new(state)),
+        }
+    }
+}
+
+#[wasm_bindgen]
+impl NoiseSession {
+    #[wasm_bindgen(constructor)]
+    pub fn new(
+        public_key: &[u8],
+        noise_header: &[u8],
+        routing_info: Option<Vec<u8>>,
+        frame_limits: Option<FrameLimits>,
+    ) -> Result<NoiseSession, JsValue> {
+        SessionState::new(public_key, noise_header, routing_info, frame_limits).map(Into::into)
+    }
+
+    /// Creates the server side of Noise_XX, e.g. for an in-process mock
+    /// server. The initiator's intro header, with or without routing info,
+    /// is dropped from the first bytes passed to `decodeFrame`.
+    pub fn responder(
+        noise_header: &[u8],
+        frame_limits: Option<FrameLimits>,
+    ) -> Result<NoiseSession, JsValue> {
+        SessionState::responder(noise_header, frame_limits).map(Into::into)
+    }
+
+    /// Responder step 1: generates our ephemeral and encrypts our static key
+    /// and certificate for the initiator's `ClientHello` ephemeral.
+    #[wasm_bindgen(js_name = respondHello)]
+    pub fn respond_hello(
+        &self,
+        client_ephemeral: &[u8],
+        static_private_key: &[u8],
+        static_public_key: &[u8],
+        certificate: &[u8],
+    ) -> Result<ServerHelloParts, JsValue> {
+        self.state.borrow_mut().respond_hello(
+            client_ephemeral,
+            static_private_key,
+            static_public_key,
+            certificate,
+        )
+    }
+
+    /// Responder step 2: decrypts and authenticates the initiator's static
+    /// key and payload from `ClientFinish`. Call `finishInit` afterwards.
+    #[wasm_bindgen(js_name = processClientFinish)]
+    pub fn process_client_finish(
+        &self,
+        encrypted_static: &[u8],
+        encrypted_payload: &[u8],
+    ) -> Result<ClientFinishParts, JsValue> {
+        self.state
+            .borrow_mut()
+            .process_client_finish(encrypted_static, encrypted_payload)
+    }
+
+    /// Updates the session hash with the given data (no-op after handshake).
+    pub fn authenticate(&self, data: &[u8]) {
+        self.state.borrow_mut().authenticate(data)
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<Uint8Array, JsValue> {
+        self.state.borrow_mut().encrypt(plaintext)
+    }
+
+    pub fn decrypt(&self, ciphertext: &[u8]) -> Result<Uint8Array, JsValue> {
+        self.state.borrow_mut().decrypt(ciphertext)
+    }
+
+    #[wasm_bindgen(js_name = mixIntoKey)]
+    pub fn mix_into_key(&self, data: &[u8]) -> Result<(), JsValue> {
+        self.state.borrow_mut().mix_into_key(data)
+    }
+
+    #[wasm_bindgen(js_name = finishInit)]
+    pub fn finish_init(&self) -> Result<(), JsValue> {
+        self.state.borrow_mut().finish_init()
+    }
+
+    /// Snapshots the transport state so `NoiseSession.importState` can resume
+    /// it elsewhere.
+    ///
+    /// SENSITIVE: the snapshot holds the transport keys, enough to decrypt
+    /// and forge traffic on this connection. Only allowed after `finishInit`
+    /// and with no partially received frame.
+    #[wasm_bindgen(js_name = exportState)]
+    pub fn export_state(&self) -> Result<Uint8Array, JsValue> {
+        self.state.borrow().export_state()
+    }
+
+    /// Restores a session from `exportState()` output. The restored session
+    /// continues at the exported counters with default decode limits and
+    /// the given frame limits.
+    #[wasm_bindgen(js_name = importState)]
+    pub fn import_state(
+        state: &[u8],
+        frame_limits: Option<FrameLimits>,
+    ) -> Result<NoiseSession, JsValue> {
+        SessionState::import_state(state, frame_limits).map(Into::into)
+    }
+
+    /// Transport frame counters. Once either reaches `2^32 - 1`, that
+    /// direction throws and the connection has to be re-established.
+    #[wasm_bindgen(getter, js_name = frameCounters)]
+    pub fn frame_counters(&self) -> FrameCounters {
+        self.state.borrow().frame_counters()
+    }
+
+    #[wasm_bindgen(getter, js_name = isFinished)]
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().is_finished
+    }
+
+    #[wasm_bindgen(js_name = encodeFrameRaw)]
+    pub fn encode_frame_raw(&self, data: &[u8]) -> Result<Uint8Array, JsValue> {
+        self.state.borrow_mut().encode_frame_raw(data)
+    }
+
+    #[wasm_bindgen(js_name = encodeFrame)]
+    pub fn encode_frame(
+        &self,
+        node: EncodingNode,
+        options: Option<EncodeOptions>,
+    ) -> Result<Uint8Array, JsValue> {
+        self.state.borrow_mut().encode_frame(node, options)
+    }
+
+    #[wasm_bindgen(js_name = decodeFrame)]
+    pub fn decode_frame(&self, new_data: &[u8]) -> Result<js_sys::Array, JsValue> {
+        self.state.borrow_mut().decode_frame(new_data)
+    }
+
+    /// Like `decodeFrame`, but a frame that fails to decrypt or unmarshal is
+    /// reported in `errors` instead of discarding the rest of the batch.
+    ///
+    /// Every complete frame consumes a read counter, failed ones included, so
+    /// later frames still decrypt. A `decrypt` error means the peer or the
+    /// stream is corrupt and the connection should normally be dropped; an
+    /// `unmarshal` error only affects that frame.
+    #[wasm_bindgen(js_name = decodeFrameSettled)]
+    pub fn decode_frame_settled(&self, new_data: &[u8]) -> Result<SettledFrames, JsValue> {
+        self.state.borrow_mut().decode_frame_settled(new_data)
+    }
+
+    /// Decodes a stream of socket chunks into transport nodes. Chunks are
+    /// only read while the returned stream wants more, and a frame that
+    /// fails to decrypt or decode errors the stream and cancels `readable`.
+    /// The handshake must be finished first.
+    #[wasm_bindgen(js_name = pipeThrough)]
+    pub fn pipe_through(&self, readable: &ReadableStream) -> Result<NodeStream, JsValue> {
+        if !self.state.borrow().is_finished {
+            return Err(JsValue::from_str(
+                "pipeThrough requires a finished handshake",
+            ));
+        }
+
+        let reader = readable
+            .get_reader()
+            .unchecked_into::<ReadableStreamDefaultReader>();
+        let state = self.state.clone();
+        let pull_reader = reader.clone();
+        let pull = Closure::<dyn FnMut(ReadableStreamDefaultController) -> Promise>::new(
+            move |controller: ReadableStreamDefaultController| {
+                let state = state.clone();
+                let reader = pull_reader.clone();
+                future_to_promise(async move {
+                    match pull_nodes(&state, &reader, &controller).await {
+                        Ok(()) => Ok(JsValue::UNDEFINED),
+                        Err(e) => {
+                            let _ = reader.cancel_with_reason(&e);
+                            Err(e)
+                        }
+                    }
+                })
+            },
+        );
+        let cancel = Closure::<dyn FnMut(JsValue) -> Promise>::new(move |reason: JsValue| {
+            reader.cancel_with_reason(&reason)
+        });
+
+        let source = Object::new();
+        Reflect::set(&source, &JsValue::from_str("pull"), &pull.into_js_value())?;
+        Reflect::set(
+            &source,
+            &JsValue::from_str("cancel"),
+            &cancel.into_js_value(),
+        )?;
+        Ok(ReadableStream::new_with_underlying_source(&source)?.unchecked_into())
+    }
+
+    /// Encodes (and once the handshake is finished, encrypts) outbound nodes
+    /// into frames, in write order.
+    #[wasm_bindgen(js_name = frameEncoder)]
+    pub fn frame_encoder(
+        &self,
+        options: Option<EncodeOptions>,
+    ) -> Result<FrameEncoderStream, JsValue> {
+        let state = self.state.clone();
+        let options = options.unwrap_or_default();
+        let transform = Closure::<
+            dyn FnMut(JsValue, TransformStreamDefaultController) -> Result<(), JsValue>,
+        >::new(
+            move |chunk: JsValue, controller: TransformStreamDefaultController| {
+                let frame = state
+                    .borrow_mut()
+                    .encode_frame(chunk.unchecked_into(), Some(options.clone()))?;
+                controller.enqueue_with_chunk(&frame)
+            },
+        );
+
+        let transformer = Object::new();
+        Reflect::set(
+            &transformer,
+            &JsValue::from_str("transform"),
+            &transform.into_js_value(),
+        )?;
+        Ok(TransformStream::new_with_transformer(&transformer)?.unchecked_into())
+    }
+
+    /// Limits applied to every node decoded by `decodeFrame`.
+    #[wasm_bindgen(js_name = setDecodeLimits)]
+    pub fn set_decode_limits(&self, limits: DecodeLimits) {
+        self.state.borrow_mut().decode_limits = limits;
+    }
+
+    #[wasm_bindgen(getter, js_name = bufferedBytes)]
+    pub fn buffered_bytes(&self) -> usize {
+        self.state.borrow().frame_decoder.buffered_len()
+    }
+
+    #[wasm_bindgen(js_name = clearBuffer)]
+    pub fn clear_buffer(&self) {
+        self.state.borrow_mut().clear_buffer();
+    }
+
+    #[wasm_bindgen(js_name = getHash)]
+    pub fn get_hash(&self) -> Uint8Array {
+        self.state.borrow().get_hash()
+    }
+
+    #[wasm_bindgen(js_name = processHandshakeInit)]
+    pub fn process_handshake_init(
+        &self,
+        server_ephemeral: &[u8],
+        server_static_encrypted: &[u8],
+        server_payload_encrypted: &[u8],
+        private_key: &[u8],
+    ) -> Result<Uint8Array, JsValue> {
+        self.state.borrow_mut().process_handshake_init(
+            server_ephemeral,
+            server_static_encrypted,
+            server_payload_encrypted,
+            private_key,
+        )
+    }
+
+    #[wasm_bindgen(js_name = processHandshakeFinish)]
+    pub fn process_handshake_finish(
+        &self,
+        noise_public_key: &[u8],
+        noise_private_key: &[u8],
+        server_ephemeral: &[u8],
+    ) -> Result<Uint8Array, JsValue> {
+        self.state.borrow_mut().process_handshake_finish(
+            noise_public_key,
+            noise_private_key,
+            server_ephemeral,
+        )
+    }
+
+    /// Sends `ClientHello` with our ephemeral key. `ephemeralKeyPair` must be
+    /// the pair whose public key was passed to the constructor.
+    #[wasm_bindgen(js_name = startHandshake)]
+    pub fn start_handshake(&self, ephemeral_key_pair: KeyPair) -> Result<Uint8Array, JsValue> {
+        self.state.borrow_mut().start_handshake(ephemeral_key_pair)
+    }
+
+    /// Processes the `ServerHello` frame returned by `decodeFrame`, sends
+    /// `ClientFinish` with our noise key and `clientPayload` (an encoded
+    /// `ClientPayload`), then switches to transport mode via `finishInit`.
+    /// The decrypted server certificate is kept in `serverCertificate`.
+    ///
+    /// The certificate chain is checked first, against WhatsApp's root key
+    /// unless `options` names another, and nothing is sent if it is invalid.
+    /// Pass `{ skipVerification: true }` to opt out.
+    #[wasm_bindgen(js_name = completeHandshake)]
+    pub fn complete_handshake(
+        &self,
+        server_hello_frame: &[u8],
+        noise_key_pair: KeyPair,
+        client_payload: &[u8],
+        options: Option<HandshakeCertificateOptions>,
+    ) -> Result<Uint8Array, JsValue> {
+        self.state.borrow_mut().complete_handshake(
+            server_hello_frame,
+            noise_key_pair,
+            client_payload,
+            options.unwrap_or_default(),
+        )
+    }
+
+    /// The decrypted `ServerHello` certificate payload, once it has been processed.
+    #[wasm_bindgen(getter, js_name = serverCertificate)]
+    pub fn server_certificate(&self) -> Option<Uint8Array> {
+        self.state
+            .borrow()
+            .server_certificate
+            .as_deref()
+            .map(Uint8Array::from)
+    }
+
+    /// Verifies `serverCertificate` and binds its leaf key to the server
+    /// static key from the same `ServerHello`.
+    #[wasm_bindgen(js_name = verifyServerCertificate)]
+    pub fn verify_server_certificate(
+        &self,
+        options: Option<CertificateOptions>,
+    ) -> Result<CertificateVerification, JsValue> {
+        self.state.borrow().verify_server_certificate(options)
+    }
+}
+
+/// Reads socket chunks until at least one node is decoded or the input ends.
+/// Nodes decoded before a failing frame are still enqueued.
+async fn pull_nodes(
+    state: &RefCell<SessionState>,
+    reader: &ReadableStreamDefaultReader,
+    controller: &ReadableStreamDefaultController,
+) -> Result<(), JsValue> {
+    let done_key = JsValue::from_str("done");
+    let value_key = JsValue::from_str("value");
+    let busy = || JsValue::from_str("pipeThrough: the NoiseSession is already in use");
+    loop {
+        let result = JsFuture::from(reader.read()).await?;
+        if Reflect::get(&result, &done_key)?.as_bool().unwrap_or(false) {
+            let session = state.try_borrow().map_err(|_| busy())?;
+            if session.frame_decoder.buffered_len() > 0 {
+                return Err(JsValue::from_str(
+                    "pipeThrough: input ended with a truncated frame",
+                ));
+            }
+            return controller.close();
+        }
+
+        let value = Reflect::get(&result, &value_key)?;
+        if value.is_undefined() || value.is_null() {
+            continue;
+        }
+        let chunk = value
+            .dyn_into::<Uint8Array>()
+            .map_err(|_| JsValue::from_str("pipeThrough: expected Uint8Array chunks"))?;
+
+        let mut enqueued = false;
+        state
+            .try_borrow_mut()
+            .map_err(|_| busy())?
+            .decode_chunk(&chunk, |frame| {
+                let node = frame.map_err(|(_, e)| e)?;
+                enqueued = true;
+                controller.enqueue_with_chunk(&node)
+            })?;
+        if enqueued {
+            return Ok(());
+        }
+    }
+}
+
+/// Everything a `NoiseSession` owns, shared with the streams it creates.
+struct SessionState {
+    role: NoiseRole,
+    handshake: Option<Handshake>,
+    /// Kept after `finishInit` for `exportState`.
+    transport_keys: Option<TransportKeys>,
     enc_cipher: Option<NoiseCipher>,
     dec_cipher: Option<NoiseCipher>,
     read_counter: u32,
     write_counter: u32,
     is_finished: bool,
     intro_header: Option<Vec<u8>>,
+    /// The initiator's intro header still to be dropped (responder only).
+    intro_reader: Option<IntroReader>,
+    /// The ephemeral public key given to the constructor (initiator only).
+    ephemeral_public_key: Option<Vec<u8>>,
+    /// Our handshake ephemeral private key, kept until it is last needed.
+    ephemeral_private_key: Option<Vec<u8>>,
+    server_certificate: Option<Vec<u8>>,
+    /// The server static key decrypted from `ServerHello`, for certificate binding.
+    server_static_key: Option<Vec<u8>>,
     frame_decoder: FrameDecoder,
     encode_scratch: Vec<u8>,
+    /// Holds each `pipeThrough` chunk while it is decoded.
+    chunk_scratch: Vec<u8>,
+    decode_limits: DecodeLimits,
 }
 
-#[wasm_bindgen]
-impl NoiseSession {
-    #[wasm_bindgen(constructor)]
-    pub fn new(
+impl SessionState {
+    fn new(
         public_key: &[u8],
         noise_header: &[u8],
         routing_info: Option<Vec<u8>>,
-    ) -> Result<NoiseSession, JsValue> {
-        let mut handshake = NoiseHandshake::new(NOISE_MODE, noise_header)
+        frame_limits: Option<FrameLimits>,
+    ) -> Result<Self, JsValue> {
+        let mut handshake = Handshake::new(noise_header, NoiseRole::Initiator)
             .map_err(|e| JsValue::from_str(&format!("NoiseHandshake init failed: {}", e)))?;
 
         handshake.authenticate(public_key);
 
         let (intro_header, _) = build_handshake_header(routing_info.as_deref());
 
-        Ok(NoiseSession {
+        Ok(Self {
+            role: handshake.role(),
             handshake: Some(handshake),
+            transport_keys: None,
             enc_cipher: None,
             dec_cipher: None,
             read_counter: 0,
             write_counter: 0,
             is_finished: false,
             intro_header: Some(intro_header),
-            frame_decoder: FrameDecoder::new(),
+            intro_reader: None,
+            ephemeral_public_key: Some(raw_public_key(public_key).to_vec()),
+            ephemeral_private_key: None,
+            server_certificate: None,
+            server_static_key: None,
+            frame_decoder: FrameDecoder::new(frame_limits.unwrap_or_default()),
             encode_scratch: Vec::with_capacity(4096),
+            chunk_scratch: Vec::new(),
+            decode_limits: DecodeLimits::default(),
         })
     }
 
-    /// Updates the session hash with the given data (no-op after handshake).
-    pub fn authenticate(&mut self, data: &[u8]) {
+    fn responder(noise_header: &[u8], frame_limits: Option<FrameLimits>) -> Result<Self, JsValue> {
+        let handshake = Handshake::new(noise_header, NoiseRole::Responder)
+            .map_err(|e| JsValue::from_str(&format!("NoiseHandshake init failed: {}", e)))?;
+
+        Ok(Self {
+            role: handshake.role(),
+            handshake: Some(handshake),
+            transport_keys: None,
+            enc_cipher: None,
+            dec_cipher: None,
+            read_counter: 0,
+            write_counter: 0,
+            is_finished: false,
+            intro_header: None,
+            intro_reader: Some(IntroReader {
+                noise_header: noise_header.to_vec(),
+                buffered: Vec::new(),
+            }),
+            ephemeral_public_key: None,
+            ephemeral_private_key: None,
+            server_certificate: None,
+            server_static_key: None,
+            frame_decoder: FrameDecoder::new(frame_limits.unwrap_or_default()),
+            encode_scratch: Vec::with_capacity(4096),
+            chunk_scratch: Vec::new(),
+            decode_limits: DecodeLimits::default(),
+        })
+    }
+
+    fn responder_handshake(&mut self) -> Result<&mut Handshake, JsValue> {
+        match self.handshake.as_mut() {
+            Some(handshake) if handshake.role() == NoiseRole::Responder => Ok(handshake),
+            Some(_) => Err(JsValue::from_str("Only a responder session can do this")),
+            None => Err(JsValue::from_str("NoiseHandshake not initialized")),
+        }
+    }
+
+    fn respond_hello(
+        &mut self,
+        client_ephemeral: &[u8],
+        static_private_key: &[u8],
+        static_public_key: &[u8],
+        certificate: &[u8],
+    ) -> Result<ServerHelloParts, JsValue> {
+        let ephemeral = generate_key_pair();
+        let ephemeral_public = raw_public_key(&ephemeral.pub_key).to_vec();
+        let handshake = self.responder_handshake()?;
+
+        handshake.authenticate(client_ephemeral);
+        handshake.authenticate(&ephemeral_public);
+        handshake
+            .mix_shared_secret(&ephemeral.priv_key, client_ephemeral)
+            .map_err(|e| JsValue::from_str(&format!("mix_shared_secret failed: {}", e)))?;
+
+        let static_key = handshake
+            .encrypt(static_public_key)
+            .map_err(|e| JsValue::from_str(&format!("encrypt static failed: {}", e)))?;
+        handshake
+            .mix_shared_secret(static_private_key, client_ephemeral)
+            .map_err(|e| JsValue::from_str(&format!("mix_shared_secret failed: {}", e)))?;
+
+        let payload = handshake
+            .encrypt(certificate)
+            .map_err(|e| JsValue::from_str(&format!("encrypt payload failed: {}", e)))?;
+
+        self.ephemeral_private_key = Some(ephemeral.priv_key);
+        Ok(ServerHelloParts {
+            ephemeral: ephemeral_public,
+            static_key,
+            payload,
+        })
+    }
+
+    fn process_client_finish(
+        &mut self,
+        encrypted_static: &[u8],
+        encrypted_payload: &[u8],
+    ) -> Result<ClientFinishParts, JsValue> {
+        let ephemeral_private_key = self
+            .ephemeral_private_key
+            .take()
+            .ok_or_else(|| JsValue::from_str("respondHello must be called first"))?;
+        let handshake = self.responder_handshake()?;
+
+        let static_key = handshake
+            .decrypt(encrypted_static)
+            .map_err(|e| JsValue::from_str(&format!("decrypt static failed: {}", e)))?;
+        handshake
+            .mix_shared_secret(&ephemeral_private_key, &static_key)
+            .map_err(|e| JsValue::from_str(&format!("mix_shared_secret failed: {}", e)))?;
+
+        let payload = handshake
+            .decrypt(encrypted_payload)
+            .map_err(|e| JsValue::from_str(&format!("decrypt payload failed: {}", e)))?;
+
+        Ok(ClientFinishParts {
+            static_key,
+            payload,
+        })
+    }
+
+    fn authenticate(&mut self, data: &[u8]) {
         if let Some(ref mut handshake) = self.handshake {
             handshake.authenticate(data);
         }
@@ -65,7 +741,7 @@ impl NoiseSession {
                 .as_ref()
                 .ok_or_else(|| JsValue::from_str("Encryption cipher not initialized"))?;
             let counter = self.write_counter;
-            self.write_counter += 1;
+            self.write_counter = next_counter(counter, "write")?;
             cipher
                 .encrypt_with_counter(counter, plaintext)
                 .map_err(|e| JsValue::from_str(&format!("Encryption failed: {}", e)))
@@ -86,7 +762,7 @@ impl NoiseSession {
                 .as_ref()
                 .ok_or_else(|| JsValue::from_str("Decryption cipher not initialized"))?;
             let counter = self.read_counter;
-            self.read_counter += 1;
+            self.read_counter = next_counter(counter, "read")?;
             let mut buf = ciphertext.to_vec();
             cipher
                 .decrypt_in_place_with_counter(counter, &mut buf)
@@ -101,22 +777,21 @@ impl NoiseSession {
         }
     }
 
-    pub fn encrypt(&mut self, plaintext: &[u8]) -> Result<Uint8Array, JsValue> {
+    fn encrypt(&mut self, plaintext: &[u8]) -> Result<Uint8Array, JsValue> {
         let ciphertext = self.encrypt_vec(plaintext)?;
         let result = Uint8Array::new_with_length(ciphertext.len() as u32);
         result.copy_from(&ciphertext);
         Ok(result)
     }
 
-    pub fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Uint8Array, JsValue> {
+    fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Uint8Array, JsValue> {
         let plaintext = self.decrypt_vec(ciphertext)?;
         let result = Uint8Array::new_with_length(plaintext.len() as u32);
         result.copy_from(&plaintext);
         Ok(result)
     }
 
-    #[wasm_bindgen(js_name = mixIntoKey)]
-    pub fn mix_into_key(&mut self, data: &[u8]) -> Result<(), JsValue> {
+    fn mix_into_key(&mut self, data: &[u8]) -> Result<(), JsValue> {
         let handshake = self
             .handshake
             .as_mut()
@@ -127,17 +802,20 @@ impl NoiseSession {
         Ok(())
     }
 
-    #[wasm_bindgen(js_name = finishInit)]
-    pub fn finish_init(&mut self) -> Result<(), JsValue> {
+    fn finish_init(&mut self) -> Result<(), JsValue> {
         let handshake = self
             .handshake
             .take()
             .ok_or_else(|| JsValue::from_str("NoiseHandshake not initialized"))?;
 
-        let (write_cipher, read_cipher) = handshake
+        let keys = handshake
             .finish()
             .map_err(|e| JsValue::from_str(&format!("finishInit failed: {}", e)))?;
+        let (write_cipher, read_cipher) = keys
+            .ciphers()
+            .map_err(|e| JsValue::from_str(&format!("finishInit failed: {}", e)))?;
 
+        self.transport_keys = Some(keys);
         self.enc_cipher = Some(write_cipher);
         self.dec_cipher = Some(read_cipher);
         self.read_counter = 0;
@@ -146,13 +824,76 @@ impl NoiseSession {
         Ok(())
     }
 
-    #[wasm_bindgen(getter, js_name = isFinished)]
-    pub fn is_finished(&self) -> bool {
-        self.is_finished
+    fn export_state(&self) -> Result<Uint8Array, JsValue> {
+        let keys = match (&self.transport_keys, self.is_finished) {
+            (Some(keys), true) => keys.clone(),
+            _ => {
+                return Err(JsValue::from_str(
+                    "exportState failed: handshake is not finished",
+                ));
+            }
+        };
+        if self.frame_decoder.buffered_len() > 0 {
+            return Err(JsValue::from_str(
+                "exportState failed: a partial frame is buffered",
+            ));
+        }
+
+        let state = NoiseState {
+            role: self.role,
+            keys,
+            read_counter: self.read_counter,
+            write_counter: self.write_counter,
+        };
+        Ok(Uint8Array::from(state.to_bytes().as_slice()))
     }
 
-    #[wasm_bindgen(js_name = encodeFrameRaw)]
-    pub fn encode_frame_raw(&mut self, data: &[u8]) -> Result<Uint8Array, JsValue> {
+    fn import_state(state: &[u8], frame_limits: Option<FrameLimits>) -> Result<Self, JsValue> {
+        let state = NoiseState::from_bytes(state)
+            .map_err(|e| JsValue::from_str(&format!("importState failed: {}", e)))?;
+        let (enc_cipher, dec_cipher) = state
+            .keys
+            .ciphers()
+            .map_err(|e| JsValue::from_str(&format!("importState failed: {}", e)))?;
+
+        Ok(Self {
+            role: state.role,
+            handshake: None,
+            transport_keys: Some(state.keys),
+            enc_cipher: Some(enc_cipher),
+            dec_cipher: Some(dec_cipher),
+            read_counter: state.read_counter,
+            write_counter: state.write_counter,
+            is_finished: true,
+            intro_header: None,
+            intro_reader: None,
+            ephemeral_public_key: None,
+            ephemeral_private_key: None,
+            server_certificate: None,
+            server_static_key: None,
+            frame_decoder: FrameDecoder::new(frame_limits.unwrap_or_default()),
+            encode_scratch: Vec::with_capacity(4096),
+            chunk_scratch: Vec::new(),
+            decode_limits: DecodeLimits::default(),
+        })
+    }
+
+    fn frame_counters(&self) -> FrameCounters {
+        FrameCounters {
+            read: self.read_counter,
+            write: self.write_counter,
+            remaining: u32::MAX - self.read_counter.max(self.write_counter),
+        }
+    }
+
+    /// Checked before encrypting so an oversized payload does not use up a nonce.
+    fn check_outbound_len(&self, plaintext_len: usize) -> Result<(), JsValue> {
+        let tag_len = if self.is_finished { GCM_TAG_LEN } else { 0 };
+        check_frame_len(plaintext_len + tag_len)
+    }
+
+    fn encode_frame_raw(&mut self, data: &[u8]) -> Result<Uint8Array, JsValue> {
+        self.check_outbound_len(data.len())?;
         let encrypted = if self.is_finished {
             self.encrypt_vec(data)?
         } else {
@@ -168,11 +909,13 @@ impl NoiseSession {
         Ok(result)
     }
 
-    #[wasm_bindgen(js_name = encodeFrame)]
-    pub fn encode_frame(&mut self, node: EncodingNode) -> Result<Uint8Array, JsValue> {
-        let node_ref = js_to_node_ref(&node)?;
-        let encoded_bytes = marshal_ref(&node_ref)
-            .map_err(|e| JsValue::from_str(&format!("Marshal error: {}", e)))?;
+    fn encode_frame(
+        &mut self,
+        node: EncodingNode,
+        options: Option<EncodeOptions>,
+    ) -> Result<Uint8Array, JsValue> {
+        let encoded_bytes = marshal_js_node(&node, &options.unwrap_or_default())?;
+        self.check_outbound_len(encoded_bytes.len())?;
 
         let encrypted = if self.is_finished {
             self.encrypt_vec(&encoded_bytes)?
@@ -189,38 +932,98 @@ impl NoiseSession {
         Ok(result)
     }
 
-    #[wasm_bindgen(js_name = decodeFrame)]
-    pub fn decode_frame(&mut self, new_data: &[u8]) -> Result<js_sys::Array, JsValue> {
-        self.frame_decoder.feed(new_data);
-        let decoded_frames = js_sys::Array::new();
+    /// Buffers `new_data`, unless it would break a `FrameLimits` bound.
+    fn feed(&mut self, new_data: &[u8]) -> Result<(), JsValue> {
+        let data = match self.intro_reader.as_mut() {
+            Some(intro) => match intro.read(new_data)? {
+                Some(rest) => {
+                    self.intro_reader = None;
+                    rest
+                }
+                None => return Ok(()),
+            },
+            None => new_data,
+        };
+        self.frame_decoder.feed(data)
+    }
 
+    fn clear_buffer(&mut self) {
+        self.frame_decoder.clear();
+    }
+
+    /// Decrypts and unmarshals one frame, or returns it raw before `finishInit`.
+    fn decode_payload(&mut self, frame_data: &[u8]) -> Result<JsValue, (FrameStage, JsValue)> {
+        if !self.is_finished {
+            return Ok(Uint8Array::from(frame_data).into());
+        }
+        let decrypted_bytes = self
+            .decrypt_vec(frame_data)
+            .map_err(|e| (FrameStage::Decrypt, e))?;
+        decode_node(decrypted_bytes, Some(self.decode_limits.clone()))
+            .map(Into::into)
+            .map_err(|e| (FrameStage::Unmarshal, e))
+    }
+
+    /// Buffers `new_data` and hands every completed frame to `each`, in
+    /// order, stopping at the first error `each` returns.
+    fn for_each_frame(
+        &mut self,
+        new_data: &[u8],
+        mut each: impl FnMut(Result<JsValue, (FrameStage, JsValue)>) -> Result<(), JsValue>,
+    ) -> Result<(), JsValue> {
+        self.feed(new_data)?;
         while let Some(frame_data) = self.frame_decoder.decode_frame() {
-            if self.is_finished {
-                let decrypted_bytes = self.decrypt_vec(&frame_data)?;
-                let node = decode_node(decrypted_bytes)?;
-                decoded_frames.push(&node.into());
-            } else {
-                let result = Uint8Array::new_with_length(frame_data.len() as u32);
-                result.copy_from(&frame_data);
-                decoded_frames.push(&result.into());
-            }
+            each(self.decode_payload(&frame_data))?;
         }
+        Ok(())
+    }
 
-        Ok(decoded_frames)
+    /// Like `for_each_frame` for a JS chunk, copied into a reused buffer.
+    fn decode_chunk(
+        &mut self,
+        chunk: &Uint8Array,
+        each: impl FnMut(Result<JsValue, (FrameStage, JsValue)>) -> Result<(), JsValue>,
+    ) -> Result<(), JsValue> {
+        let mut scratch = std::mem::take(&mut self.chunk_scratch);
+        scratch.resize(chunk.length() as usize, 0);
+        chunk.copy_to(&mut scratch);
+        let result = self.for_each_frame(&scratch, each);
+        self.chunk_scratch = scratch;
+        result
     }
 
-    #[wasm_bindgen(getter, js_name = bufferedBytes)]
-    pub fn buffered_bytes(&self) -> usize {
-        self.frame_decoder.buffered_len()
+    fn decode_frame(&mut self, new_data: &[u8]) -> Result<js_sys::Array, JsValue> {
+        let decoded_frames = js_sys::Array::new();
+        self.for_each_frame(new_data, |frame| {
+            decoded_frames.push(&frame.map_err(|(_, e)| e)?);
+            Ok(())
+        })?;
+        Ok(decoded_frames)
     }
 
-    #[wasm_bindgen(js_name = clearBuffer)]
-    pub fn clear_buffer(&mut self) {
-        self.frame_decoder.clear();
+    fn decode_frame_settled(&mut self, new_data: &[u8]) -> Result<SettledFrames, JsValue> {
+        let nodes = js_sys::Array::new();
+        let mut errors = Vec::new();
+
+        self.for_each_frame(new_data, |frame| {
+            match frame {
+                Ok(frame) => nodes.push(&frame),
+                Err((stage, e)) => {
+                    errors.push(FrameError {
+                        index: nodes.length(),
+                        stage,
+                        message: error_message(&e),
+                    });
+                    nodes.push(&JsValue::UNDEFINED)
+                }
+            };
+            Ok(())
+        })?;
+
+        Ok(SettledFrames { nodes, errors })
     }
 
-    #[wasm_bindgen(js_name = getHash)]
-    pub fn get_hash(&self) -> Uint8Array {
+    fn get_hash(&self) -> Uint8Array {
         if let Some(ref handshake) = self.handshake {
             let hash = handshake.hash();
             let result = Uint8Array::new_with_length(hash.len() as u32);
@@ -231,14 +1034,13 @@ impl NoiseSession {
         }
     }
 
-    #[wasm_bindgen(js_name = processHandshakeInit)]
-    pub fn process_handshake_init(
+    fn handshake_init(
         &mut self,
         server_ephemeral: &[u8],
         server_static_encrypted: &[u8],
         server_payload_encrypted: &[u8],
         private_key: &[u8],
-    ) -> Result<Uint8Array, JsValue> {
+    ) -> Result<Vec<u8>, JsValue> {
         let handshake = self
             .handshake
             .as_mut()
@@ -258,22 +1060,21 @@ impl NoiseSession {
             .mix_shared_secret(private_key, &dec_static)
             .map_err(|e| JsValue::from_str(&format!("mix_shared_secret failed: {}", e)))?;
 
-        let cert_payload = handshake
+        let certificate = handshake
             .decrypt(server_payload_encrypted)
             .map_err(|e| JsValue::from_str(&format!("decrypt payload failed: {}", e)))?;
 
-        let result = Uint8Array::new_with_length(cert_payload.len() as u32);
-        result.copy_from(&cert_payload);
-        Ok(result)
+        self.server_static_key = Some(dec_static);
+        self.server_certificate = Some(certificate.clone());
+        Ok(certificate)
     }
 
-    #[wasm_bindgen(js_name = processHandshakeFinish)]
-    pub fn process_handshake_finish(
+    fn handshake_finish(
         &mut self,
         noise_public_key: &[u8],
         noise_private_key: &[u8],
         server_ephemeral: &[u8],
-    ) -> Result<Uint8Array, JsValue> {
+    ) -> Result<Vec<u8>, JsValue> {
         let handshake = self
             .handshake
             .as_mut()
@@ -287,8 +1088,149 @@ impl NoiseSession {
             .mix_shared_secret(noise_private_key, server_ephemeral)
             .map_err(|e| JsValue::from_str(&format!("mix_shared_secret failed: {}", e)))?;
 
+        Ok(encrypted_key)
+    }
+
+    fn process_handshake_init(
+        &mut self,
+        server_ephemeral: &[u8],
+        server_static_encrypted: &[u8],
+        server_payload_encrypted: &[u8],
+        private_key: &[u8],
+    ) -> Result<Uint8Array, JsValue> {
+        let cert_payload = self.handshake_init(
+            server_ephemeral,
+            server_static_encrypted,
+            server_payload_encrypted,
+            private_key,
+        )?;
+
+        let result = Uint8Array::new_with_length(cert_payload.len() as u32);
+        result.copy_from(&cert_payload);
+        Ok(result)
+    }
+
+    fn process_handshake_finish(
+        &mut self,
+        noise_public_key: &[u8],
+        noise_private_key: &[u8],
+        server_ephemeral: &[u8],
+    ) -> Result<Uint8Array, JsValue> {
+        let encrypted_key =
+            self.handshake_finish(noise_public_key, noise_private_key, server_ephemeral)?;
+
         let result = Uint8Array::new_with_length(encrypted_key.len() as u32);
         result.copy_from(&encrypted_key);
         Ok(result)
     }
+
+    fn start_handshake(&mut self, ephemeral_key_pair: KeyPair) -> Result<Uint8Array, JsValue> {
+        if self.handshake.as_ref().map(Handshake::role) != Some(NoiseRole::Initiator) {
+            return Err(JsValue::from_str(
+                "startHandshake requires an initiator session before finishInit",
+            ));
+        }
+
+        let ephemeral_public = raw_public_key(&ephemeral_key_pair.pub_key);
+        if self.ephemeral_public_key.as_deref() != Some(ephemeral_public) {
+            return Err(JsValue::from_str(
+                "startHandshake failed: ephemeralKeyPair does not match the constructor public key",
+            ));
+        }
+
+        let hello = HandshakeMessage {
+            client_hello: Some(ClientHello {
+                ephemeral: Some(ephemeral_public.to_vec()),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        self.ephemeral_private_key = Some(ephemeral_key_pair.priv_key);
+        self.encode_frame_raw(&hello.encode_to_vec())
+    }
+
+    fn complete_handshake(
+        &mut self,
+        server_hello_frame: &[u8],
+        noise_key_pair: KeyPair,
+        client_payload: &[u8],
+        options: HandshakeCertificateOptions,
+    ) -> Result<Uint8Array, JsValue> {
+        if self.ephemeral_private_key.is_none() {
+            return Err(JsValue::from_str("startHandshake must be called first"));
+        }
+
+        let server_hello = HandshakeMessage::decode(server_hello_frame)
+            .ok()
+            .and_then(|message| message.server_hello)
+            .ok_or_else(|| JsValue::from_str("completeHandshake failed: missing ServerHello"))?;
+        let (Some(server_ephemeral), Some(server_static), Some(server_payload)) = (
+            server_hello.ephemeral,
+            server_hello.r#static,
+            server_hello.payload,
+        ) else {
+            return Err(JsValue::from_str(
+                "completeHandshake failed: incomplete ServerHello",
+            ));
+        };
+
+        // Only consumed once the ServerHello is well-formed, so a garbled
+        // frame can be followed by the real one.
+        let ephemeral_private_key = self
+            .ephemeral_private_key
+            .take()
+            .ok_or_else(|| JsValue::from_str("startHandshake must be called first"))?;
+        self.handshake_init(
+            &server_ephemeral,
+            &server_static,
+            &server_payload,
+            &ephemeral_private_key,
+        )?;
+        if !options.skip_verification.unwrap_or(false) {
+            let verification = self.verify_server_certificate(Some(options.into()))?;
+            if let Some(error) = verification.error {
+                return Err(JsValue::from_str(&format!(
+                    "completeHandshake failed: invalid server certificate ({})",
+                    error.as_str()
+                )));
+            }
+        }
+        let encrypted_key = self.handshake_finish(
+            raw_public_key(&noise_key_pair.pub_key),
+            &noise_key_pair.priv_key,
+            &server_ephemeral,
+        )?;
+        let encrypted_payload = self.encrypt_vec(client_payload)?;
+
+        let finish = HandshakeMessage {
+            client_finish: Some(ClientFinish {
+                r#static: Some(encrypted_key),
+                payload: Some(encrypted_payload),
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        let frame = self.encode_frame_raw(&finish.encode_to_vec())?;
+
+        self.finish_init()?;
+        Ok(frame)
+    }
+
+    fn verify_server_certificate(
+        &self,
+        options: Option<CertificateOptions>,
+    ) -> Result<CertificateVerification, JsValue> {
+        let (Some(certificate), Some(server_static_key)) =
+            (&self.server_certificate, &self.server_static_key)
+        else {
+            return Err(JsValue::from_str(
+                "verifyServerCertificate failed: no ServerHello has been processed",
+            ));
+        };
+        Ok(verify_chain(
+            certificate,
+            server_static_key,
+            &options.unwrap_or_default(),
+        ))
+    }
 }
@@ -0,0 +1,184 @@
+use std::fmt::Display;
+use wacore_binary::consts::NOISE_PATTERN_XX as NOISE_MODE;
+use wacore_noise::{NoiseCipher, NoiseHandshake};
+
+const STATE_MAGIC: &[u8; 4] = b"WANS";
+const STATE_VERSION: u8 = 1;
+const KEY_LEN: usize = 32;
+
+/// Which side of Noise_XX a session plays. The responder uses the
+/// initiator's write key for reading and vice versa.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NoiseRole {
+    Initiator,
+    Responder,
+}
+
+/// This side's transport keys, derived by `finishInit`.
+#[derive(Clone)]
+pub(crate) struct TransportKeys {
+    pub write: [u8; KEY_LEN],
+    pub read: [u8; KEY_LEN],
+}
+
+impl TransportKeys {
+    /// The (write, read) transport ciphers.
+    pub(crate) fn ciphers(&self) -> Result<(NoiseCipher, NoiseCipher), String> {
+        Ok((
+            NoiseCipher::new(&self.write).map_err(describe)?,
+            NoiseCipher::new(&self.read).map_err(describe)?,
+        ))
+    }
+}
+
+fn describe(e: impl Display) -> String {
+    e.to_string()
+}
+
+/// A `NoiseHandshake` and the side it is played from.
+pub(crate) struct Handshake {
+    inner: NoiseHandshake,
+    role: NoiseRole,
+}
+
+impl Handshake {
+    pub(crate) fn new(noise_header: &[u8], role: NoiseRole) -> Result<Self, String> {
+        let inner = NoiseHandshake::new(NOISE_MODE, noise_header).map_err(describe)?;
+        Ok(Self { inner, role })
+    }
+
+    pub(crate) fn authenticate(&mut self, data: &[u8]) {
+        self.inner.authenticate(data);
+    }
+
+    pub(crate) fn mix_into_key(&mut self, data: &[u8]) -> Result<(), String> {
+        self.inner.mix_into_key(data).map_err(describe)
+    }
+
+    pub(crate) fn mix_shared_secret(
+        &mut self,
+        private_key: &[u8],
+        public_key: &[u8],
+    ) -> Result<(), String> {
+        self.inner
+            .mix_shared_secret(private_key, public_key)
+            .map_err(describe)
+    }
+
+    pub(crate) fn encrypt(&mut self, plaintext: &[u8]) -> Result<Vec<u8>, String> {
+        self.inner.encrypt(plaintext).map_err(describe)
+    }
+
+    pub(crate) fn decrypt(&mut self, ciphertext: &[u8]) -> Result<Vec<u8>, String> {
+        self.inner.decrypt(ciphertext).map_err(describe)
+    }
+
+    pub(crate) fn hash(&self) -> &[u8] {
+        self.inner.hash()
+    }
+
+    pub(crate) fn role(&self) -> NoiseRole {
+        self.role
+    }
+
+    /// This side's transport keys: the ones `NoiseHandshake::finish` builds
+    /// its ciphers from, swapped for the responder.
+    pub(crate) fn finish(self) -> Result<TransportKeys, String> {
+        let (initiator_write, initiator_read) = self.inner.finish_keys().map_err(describe)?;
+        Ok(match self.role {
+            NoiseRole::Initiator => TransportKeys {
+                write: initiator_write,
+                read: initiator_read,
+            },
+            NoiseRole::Responder => TransportKeys {
+                write: initiator_read,
+                read: initiator_write,
+            },
+        })
+    }
+}
+
+/// Post-handshake state of a `NoiseSession`.
+pub(crate) struct NoiseState {
+    pub role: NoiseRole,
+    pub keys: TransportKeys,
+    pub read_counter: u32,
+    pub write_counter: u32,
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        if self.data.len() < len {
+            return Err("unexpected end of data".to_string());
+        }
+        let (head, tail) = self.data.split_at(len);
+        self.data = tail;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn key(&mut self) -> Result<[u8; KEY_LEN], String> {
+        Ok(self.take(KEY_LEN)?.try_into().expect("took KEY_LEN bytes"))
+    }
+}
+
+impl NoiseState {
+    /// `WANS`, version, role, read and write counters, write and read keys.
+    pub(crate) fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(STATE_MAGIC.len() + 2 + 8 + 2 * KEY_LEN);
+        out.extend_from_slice(STATE_MAGIC);
+        out.push(STATE_VERSION);
+        out.push(match self.role {
+            NoiseRole::Initiator => 0,
+            NoiseRole::Responder => 1,
+        });
+        out.extend_from_slice(&self.read_counter.to_be_bytes());
+        out.extend_from_slice(&self.write_counter.to_be_bytes());
+        out.extend_from_slice(&self.keys.write);
+        out.extend_from_slice(&self.keys.read);
+        out
+    }
+
+    pub(crate) fn from_bytes(data: &[u8]) -> Result<Self, String> {
+        let mut reader = Reader { data };
+        if reader.take(STATE_MAGIC.len())? != STATE_MAGIC {
+            return Err("not a NoiseSession state".to_string());
+        }
+        match reader.u8()? {
+            STATE_VERSION => {}
+            version => return Err(format!("unsupported state version {version}")),
+        }
+        let role = match reader.u8()? {
+            0 => NoiseRole::Initiator,
+            1 => NoiseRole::Responder,
+            role => return Err(format!("unknown role {role}")),
+        };
+
+        let read_counter = reader.u32()?;
+        let write_counter = reader.u32()?;
+        let write = reader.key()?;
+        let read = reader.key()?;
+        if !reader.data.is_empty() {
+            return Err("trailing bytes".to_string());
+        }
+
+        Ok(Self {
+            role,
+            keys: TransportKeys { write, read },
+            read_counter,
+            write_counter,
+        })
+    }
+}
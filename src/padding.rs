@@ -0,0 +1,113 @@
+use js_sys::Uint8Array;
+use rand::{Rng, rngs::StdRng};
+use std::fmt;
+use wasm_bindgen::prelude::*;
+
+use crate::js_error::typed_js_error;
+
+const MAX_PAD: usize = 16;
+
+/// Why `unpadMessage` rejected its input. Surfaces in JS as an `Error` with
+/// `name === "PaddingError"` and `reason` set to `empty`, `length` or `bytes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum PaddingError {
+    Empty,
+    InvalidLength { pad: usize, len: usize },
+    InconsistentBytes { pad: usize },
+}
+
+impl PaddingError {
+    fn reason(&self) -> &'static str {
+        match self {
+            PaddingError::Empty => "empty",
+            PaddingError::InvalidLength { .. } => "length",
+            PaddingError::InconsistentBytes { .. } => "bytes",
+        }
+    }
+}
+
+impl fmt::Display for PaddingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PaddingError::Empty => write!(f, "unpad given empty bytes"),
+            PaddingError::InvalidLength { pad, len } => {
+                write!(f, "unpad given {len} bytes, but pad is {pad}")
+            }
+            PaddingError::InconsistentBytes { pad } => {
+                write!(f, "padding bytes do not all equal the pad length {pad}")
+            }
+        }
+    }
+}
+
+impl From<PaddingError> for JsValue {
+    fn from(e: PaddingError) -> Self {
+        typed_js_error("PaddingError", "reason", e.reason(), &e.to_string())
+    }
+}
+
+/// Appends WhatsApp's random 1-15 byte padding, where every padding byte
+/// holds the padding length.
+pub(crate) fn pad_random_max16(data: &[u8]) -> Vec<u8> {
+    let mut pad = [0u8; 1];
+    rand::make_rng::<StdRng>().fill_bytes(&mut pad);
+    let pad = match pad[0] & 0x0f {
+        0 => 0x0f,
+        n => n,
+    };
+
+    let mut padded = Vec::with_capacity(data.len() + pad as usize);
+    padded.extend_from_slice(data);
+    padded.resize(data.len() + pad as usize, pad);
+    padded
+}
+
+/// Strips the padding added by [`pad_random_max16`]. The pad length must be
+/// 1-16, fit in the input, and every padding byte must repeat it.
+pub(crate) fn unpad_random_max16(data: &[u8]) -> Result<&[u8], PaddingError> {
+    let Some(&pad) = data.last() else {
+        return Err(PaddingError::Empty);
+    };
+    let pad = pad as usize;
+    if pad == 0 || pad > MAX_PAD || pad > data.len() {
+        return Err(PaddingError::InvalidLength {
+            pad,
+            len: data.len(),
+        });
+    }
+
+    let (body, padding) = data.split_at(data.len() - pad);
+    if padding.iter().any(|&b| b as usize != pad) {
+        return Err(PaddingError::InconsistentBytes { pad });
+    }
+    Ok(body)
+}
+
+/// Strips padding like Baileys' `unpadRandomMax16`: only checks that the
+/// pad length fits, for peers whose padding does not follow the strict form.
+pub(crate) fn unpad_lenient(data: &[u8]) -> Result<&[u8], PaddingError> {
+    let Some(&pad) = data.last() else {
+        return Err(PaddingError::Empty);
+    };
+    let pad = pad as usize;
+    if pad > data.len() {
+        return Err(PaddingError::InvalidLength {
+            pad,
+            len: data.len(),
+        });
+    }
+    Ok(&data[..data.len() - pad])
+}
+
+/// Pads message plaintext before `SessionCipher`/`GroupCipher` encryption.
+#[wasm_bindgen(js_name = padMessage)]
+pub fn pad_message(bytes: &[u8]) -> Uint8Array {
+    Uint8Array::from(pad_random_max16(bytes).as_slice())
+}
+
+/// Removes message padding after decryption. Throws a `PaddingError` if the
+/// trailing pad is malformed.
+#[wasm_bindgen(js_name = unpadMessage)]
+pub fn unpad_message(bytes: &[u8]) -> Result<Uint8Array, JsValue> {
+    Ok(Uint8Array::from(unpad_random_max16(bytes)?))
+}
@@ -0,0 +1,102 @@
+use js_sys::Uint8Array;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use waproto::whatsapp::{HistorySync, Message, SyncActionValue, WebMessageInfo};
+use wasm_bindgen::prelude::*;
+
+// The prost types live in `waproto`, so Tsify cannot derive typings for them.
+// `proto-types.d.ts` is generated from Baileys' WAProto typings at build time
+// (scripts/gen-proto-types.mjs, run with `bun run gen:proto`); field names
+// follow the protobuf camelCase JSON mapping used by protobufjs.
+#[wasm_bindgen(typescript_custom_section)]
+const TS_PROTO_TYPES: &str = r#"
+import type { proto } from "./proto-types";
+export type { proto };
+export type WAMessage = proto.IMessage;
+export type WAWebMessageInfo = proto.IWebMessageInfo;
+export type WAHistorySync = proto.IHistorySync;
+export type WASyncActionValue = proto.ISyncActionValue;
+
+export function encodeMessage(message: WAMessage): Uint8Array;
+export function decodeMessage(bytes: Uint8Array): WAMessage;
+export function encodeWebMessageInfo(info: WAWebMessageInfo): Uint8Array;
+export function decodeWebMessageInfo(bytes: Uint8Array): WAWebMessageInfo;
+export function encodeHistorySync(history: WAHistorySync): Uint8Array;
+export function decodeHistorySync(bytes: Uint8Array): WAHistorySync;
+export function encodeSyncActionValue(value: WASyncActionValue): Uint8Array;
+export function decodeSyncActionValue(bytes: Uint8Array): WASyncActionValue;
+"#;
+
+fn to_proto<T: DeserializeOwned>(value: JsValue, name: &str) -> Result<T, JsValue> {
+    serde_wasm_bindgen::from_value(value)
+        .map_err(|e| JsValue::from_str(&format!("Invalid {name}: {e}")))
+}
+
+/// Converts a decoded proto to a JS object, with `bytes` fields as `Uint8Array`
+/// and 64-bit integer fields as `bigint`, so values above 2^53 survive.
+pub(crate) fn from_proto<T: Serialize>(proto: &T, name: &str) -> Result<JsValue, JsValue> {
+    let serializer = serde_wasm_bindgen::Serializer::new()
+        .serialize_maps_as_objects(true)
+        .serialize_bytes_as_arrays(false)
+        .serialize_large_number_types_as_bigints(true);
+    proto
+        .serialize(&serializer)
+        .map_err(|e| JsValue::from_str(&format!("Failed to convert {name}: {e}")))
+}
+
+fn encode<T: prost::Message + DeserializeOwned>(
+    value: JsValue,
+    name: &str,
+) -> Result<Uint8Array, JsValue> {
+    let proto: T = to_proto(value, name)?;
+    Ok(Uint8Array::from(proto.encode_to_vec().as_slice()))
+}
+
+fn decode<T: prost::Message + Default + Serialize>(
+    bytes: &[u8],
+    name: &str,
+) -> Result<JsValue, JsValue> {
+    let proto = T::decode(bytes)
+        .map_err(|e| JsValue::from_str(&format!("Failed to decode {name}: {e}")))?;
+    from_proto(&proto, name)
+}
+
+#[wasm_bindgen(js_name = encodeMessage, skip_typescript)]
+pub fn encode_message(message: JsValue) -> Result<Uint8Array, JsValue> {
+    encode::<Message>(message, "Message")
+}
+
+#[wasm_bindgen(js_name = decodeMessage, skip_typescript)]
+pub fn decode_message(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    decode::<Message>(bytes, "Message")
+}
+
+#[wasm_bindgen(js_name = encodeWebMessageInfo, skip_typescript)]
+pub fn encode_web_message_info(info: JsValue) -> Result<Uint8Array, JsValue> {
+    encode::<WebMessageInfo>(info, "WebMessageInfo")
+}
+
+#[wasm_bindgen(js_name = decodeWebMessageInfo, skip_typescript)]
+pub fn decode_web_message_info(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    decode::<WebMessageInfo>(bytes, "WebMessageInfo")
+}
+
+#[wasm_bindgen(js_name = encodeHistorySync, skip_typescript)]
+pub fn encode_history_sync(history: JsValue) -> Result<Uint8Array, JsValue> {
+    encode::<HistorySync>(history, "HistorySync")
+}
+
+#[wasm_bindgen(js_name = decodeHistorySync, skip_typescript)]
+pub fn decode_history_sync(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    decode::<HistorySync>(bytes, "HistorySync")
+}
+
+#[wasm_bindgen(js_name = encodeSyncActionValue, skip_typescript)]
+pub fn encode_sync_action_value(value: JsValue) -> Result<Uint8Array, JsValue> {
+    encode::<SyncActionValue>(value, "SyncActionValue")
+}
+
+#[wasm_bindgen(js_name = decodeSyncActionValue, skip_typescript)]
+pub fn decode_sync_action_value(bytes: &[u8]) -> Result<JsValue, JsValue> {
+    decode::<SyncActionValue>(bytes, "SyncActionValue")
+}
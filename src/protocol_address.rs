@@ -1,13 +1,29 @@
-use js_sys::{JsString, Number};
+use js_sys::{JsString, Number, Object};
 use std::fmt;
 use wacore_libsignal::core::{DeviceId, ProtocolAddress as CoreProtocolAddress};
+use wasm_bindgen::convert::RefFromWasmAbi;
 use wasm_bindgen::prelude::*;
 
+use crate::exported::{exported_ref, prototype_of};
+
 const INVALID_ENCODING: &str = "Invalid address encoding";
 
 #[wasm_bindgen(js_name = ProtocolAddress)]
 pub struct ProtocolAddress(pub(crate) CoreProtocolAddress);
 
+thread_local! {
+    static PROTOCOL_ADDRESS_PROTOTYPE: Object = prototype_of(
+        ProtocolAddress(CoreProtocolAddress::new(String::new(), DeviceId::from(0))).into(),
+    );
+}
+
+impl ProtocolAddress {
+    /// Borrows the address behind `value` if it is a `ProtocolAddress`.
+    pub(crate) fn from_js(value: &JsValue) -> Option<<Self as RefFromWasmAbi>::Anchor> {
+        PROTOCOL_ADDRESS_PROTOTYPE.with(|prototype| exported_ref::<Self>(value, prototype))
+    }
+}
+
 impl fmt::Display for ProtocolAddress {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{}", self.0)
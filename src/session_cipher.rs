@@ -7,6 +7,7 @@ use crate::{
     protocol_address::ProtocolAddress,
     storage_adapter::{JsStorageAdapter, SignalStorage},
 };
+use wacore_libsignal::core::ProtocolAddress as CoreProtocolAddress;
 use wacore_libsignal::protocol::{self as libsignal, SessionStore, UsePQRatchet};
 
 #[inline]
@@ -25,6 +26,88 @@ thread_local! {
     static BODY_KEY: RefCell<JsValue> = RefCell::new(JsValue::from_str("body"));
 }
 
+/// Encrypts for an existing session; the result is a `pkmsg` until the
+/// remote side has replied. Without a usable session this fails with
+/// `SessionNotFound`.
+pub(crate) async fn encrypt_message(
+    storage_adapter: &JsStorageAdapter,
+    remote_address: &CoreProtocolAddress,
+    plaintext: &[u8],
+) -> Result<libsignal::CiphertextMessage, libsignal::SignalProtocolError> {
+    let mut session_store = storage_adapter.clone();
+    let mut identity_store = session_store.clone();
+
+    libsignal::message_encrypt(
+        plaintext,
+        remote_address,
+        &mut session_store,
+        &mut identity_store,
+    )
+    .await
+}
+
+/// Whether a session with a usable state exists for `remote_address`.
+pub(crate) async fn has_session(
+    storage_adapter: &JsStorageAdapter,
+    remote_address: &CoreProtocolAddress,
+) -> Result<bool, String> {
+    let record = SessionStore::load_session(storage_adapter, remote_address)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(record.is_some_and(|r| r.session_state().is_some()))
+}
+
+/// Decrypts a `pkmsg`, establishing the session if needed.
+pub(crate) async fn decrypt_prekey_message(
+    storage_adapter: &JsStorageAdapter,
+    remote_address: &CoreProtocolAddress,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, String> {
+    let prekey_message = libsignal::PreKeySignalMessage::try_from(ciphertext)
+        .map_err(|e| format!("Invalid PreKeyMessage format: {}", e))?;
+
+    let mut session_store = storage_adapter.clone();
+    let mut identity_store = session_store.clone();
+    let mut prekey_store = session_store.clone();
+    let signed_prekey_store = session_store.clone();
+
+    libsignal::message_decrypt_prekey(
+        &prekey_message,
+        remote_address,
+        &mut session_store,
+        &mut identity_store,
+        &mut prekey_store,
+        &signed_prekey_store,
+        &mut rand::make_rng::<StdRng>(),
+        UsePQRatchet::No,
+    )
+    .await
+    .map_err(|e| format!("{:?}", e))
+}
+
+/// Decrypts a `msg` on an existing session.
+pub(crate) async fn decrypt_signal_message(
+    storage_adapter: &JsStorageAdapter,
+    remote_address: &CoreProtocolAddress,
+    ciphertext: &[u8],
+) -> Result<Vec<u8>, String> {
+    let signal_message = libsignal::SignalMessage::try_from(ciphertext)
+        .map_err(|e| format!("Invalid WhisperMessage format: {}", e))?;
+
+    let mut session_store = storage_adapter.clone();
+    let mut identity_store = session_store.clone();
+
+    libsignal::message_decrypt_signal(
+        &signal_message,
+        remote_address,
+        &mut session_store,
+        &mut identity_store,
+        &mut rand::make_rng::<StdRng>(),
+    )
+    .await
+    .map_err(|e| format!("{:?}", e))
+}
+
 #[wasm_bindgen(js_name = SessionCipher)]
 pub struct SessionCipher {
     storage_adapter: JsStorageAdapter,
@@ -42,20 +125,13 @@ impl SessionCipher {
     }
 
     pub async fn encrypt(&mut self, plaintext: &[u8]) -> Result<EncryptResult, JsValue> {
-        let mut session_store = self.storage_adapter.clone();
-        let mut identity_store = session_store.clone();
-
-        let ciphertext_message = libsignal::message_encrypt(
-            plaintext,
-            &self.remote_address.0,
-            &mut session_store,
-            &mut identity_store,
-        )
-        .await
-        .map_err(|e| {
-            let msg = format!("SessionCipher.encrypt error: {:?}", e);
-            JsValue::from_str(&msg)
-        })?;
+        let ciphertext_message =
+            encrypt_message(&self.storage_adapter, &self.remote_address.0, plaintext)
+                .await
+                .map_err(|e| {
+                    let msg = format!("SessionCipher.encrypt error: {:?}", e);
+                    JsValue::from_str(&msg)
+                })?;
 
         let body_array = bytes_to_uint8array(ciphertext_message.serialize());
         let type_id = ciphertext_message.message_type() as u8;
@@ -72,32 +148,13 @@ impl SessionCipher {
         &mut self,
         ciphertext: &[u8],
     ) -> Result<Uint8Array, JsValue> {
-        let prekey_message = libsignal::PreKeySignalMessage::try_from(ciphertext)
-            .map_err(|e| {
-                let msg = format!("SessionCipher.decryptPreKeyWhisperMessage failed: Invalid PreKeyMessage format: {}", e);
-                JsValue::from_str(&msg)
-            })?;
-
-        let mut session_store = self.storage_adapter.clone();
-        let mut identity_store = session_store.clone();
-        let mut prekey_store = session_store.clone();
-        let signed_prekey_store = session_store.clone();
-
-        let plaintext = libsignal::message_decrypt_prekey(
-            &prekey_message,
-            &self.remote_address.0,
-            &mut session_store,
-            &mut identity_store,
-            &mut prekey_store,
-            &signed_prekey_store,
-            &mut rand::make_rng::<StdRng>(),
-            UsePQRatchet::No,
-        )
-        .await
-        .map_err(|e| {
-            let msg = format!("SessionCipher.decryptPreKeyWhisperMessage failed: {:?}", e);
-            JsValue::from_str(&msg)
-        })?;
+        let plaintext =
+            decrypt_prekey_message(&self.storage_adapter, &self.remote_address.0, ciphertext)
+                .await
+                .map_err(|e| {
+                    let msg = format!("SessionCipher.decryptPreKeyWhisperMessage failed: {}", e);
+                    JsValue::from_str(&msg)
+                })?;
 
         Ok(bytes_to_uint8array(&plaintext))
     }
@@ -107,42 +164,21 @@ impl SessionCipher {
         &mut self,
         ciphertext: &[u8],
     ) -> Result<Uint8Array, JsValue> {
-        let signal_message = libsignal::SignalMessage::try_from(ciphertext).map_err(|e| {
-            let msg = format!(
-                "SessionCipher.decryptWhisperMessage failed: Invalid WhisperMessage format: {}",
-                e
-            );
-            JsValue::from_str(&msg)
-        })?;
-
-        let mut session_store = self.storage_adapter.clone();
-        let mut identity_store = session_store.clone();
-
-        let plaintext = libsignal::message_decrypt_signal(
-            &signal_message,
-            &self.remote_address.0,
-            &mut session_store,
-            &mut identity_store,
-            &mut rand::make_rng::<StdRng>(),
-        )
-        .await
-        .map_err(|e| {
-            let msg = format!("SessionCipher.decryptWhisperMessage failed: {:?}", e);
-            JsValue::from_str(&msg)
-        })?;
+        let plaintext =
+            decrypt_signal_message(&self.storage_adapter, &self.remote_address.0, ciphertext)
+                .await
+                .map_err(|e| {
+                    let msg = format!("SessionCipher.decryptWhisperMessage failed: {}", e);
+                    JsValue::from_str(&msg)
+                })?;
 
         Ok(bytes_to_uint8array(&plaintext))
     }
 
     #[wasm_bindgen(js_name = hasOpenSession)]
     pub async fn has_open_session(&self) -> Result<bool, JsValue> {
-        let record = SessionStore::load_session(&self.storage_adapter, &self.remote_address.0)
+        has_session(&self.storage_adapter, &self.remote_address.0)
             .await
-            .map_err(|e| JsValue::from_str(&e.to_string()))?;
-
-        match record {
-            Some(r) => Ok(r.session_state().is_some()),
-            None => Ok(false),
-        }
+            .map_err(|e| JsValue::from_str(&e))
     }
 }
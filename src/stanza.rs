@@ -0,0 +1,481 @@
+use serde::{Deserialize, Serialize};
+use tsify_next::Tsify;
+use wacore_binary::node::{NodeContentRef, NodeRef};
+use wasm_bindgen::prelude::*;
+
+use crate::binary::InternalBinaryNode;
+use crate::jid::{DEFAULT_USER_SERVER, Jid};
+use crate::node_builder::BinaryNodeBuilder;
+
+/// A JID attribute together with its decoded parts.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ParsedJid {
+    pub jid: String,
+    pub user: String,
+    pub server: String,
+    pub domain_type: u32,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<u32>,
+}
+
+/// An `<enc>` child of a message.
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct EncChild {
+    /// `pkmsg`, `msg`, `skmsg`, `msmsg` or `plaintext`, as sent.
+    /// `MessageDecryptor` reports `msmsg` as unsupported.
+    #[serde(rename = "type")]
+    pub enc_type: String,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub v: Option<u32>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u32>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mediatype: Option<String>,
+    #[tsify(type = "Uint8Array")]
+    #[serde(with = "serde_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct MessageStanza {
+    pub id: String,
+    pub from: ParsedJid,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<ParsedJid>,
+    #[serde(rename = "type")]
+    pub message_type: String,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub participant: Option<ParsedJid>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<ParsedJid>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<String>,
+    /// Unix seconds from the `t` attribute.
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub t: Option<u64>,
+    /// Whether the message was delivered from the offline queue.
+    pub offline: bool,
+    pub enc: Vec<EncChild>,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptStanza {
+    pub id: String,
+    pub from: ParsedJid,
+    /// Absent for plain delivery receipts.
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "type")]
+    pub receipt_type: Option<String>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub participant: Option<ParsedJid>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipient: Option<ParsedJid>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub t: Option<u64>,
+    pub offline: bool,
+    /// `id` followed by the ids listed in `<list><item id/></list>`.
+    pub message_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct IqError {
+    pub code: u32,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct IqResult {
+    pub id: String,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<ParsedJid>,
+    /// `result` or `error`
+    #[serde(rename = "type")]
+    pub iq_type: String,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub xmlns: Option<String>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<IqError>,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct NotificationStanza {
+    pub id: String,
+    pub from: ParsedJid,
+    #[serde(rename = "type")]
+    pub notification_type: String,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub participant: Option<ParsedJid>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub notify: Option<String>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub t: Option<u64>,
+    pub offline: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Tsify)]
+#[tsify(into_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct AckStanza {
+    pub id: String,
+    /// Tag of the stanza being acknowledged.
+    pub class: String,
+    pub from: ParsedJid,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "type")]
+    pub ack_type: Option<String>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub participant: Option<ParsedJid>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub t: Option<u64>,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<u32>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct AckOptions {
+    /// Sent as the `error` attribute when non-zero.
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<u32>,
+    /// Our own JID, used as `from` when acking an `<unavailable>` message.
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub me_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Tsify)]
+#[tsify(from_wasm_abi)]
+#[serde(rename_all = "camelCase")]
+pub struct ReceiptOptions {
+    pub jid: String,
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub participant: Option<String>,
+    pub message_ids: Vec<String>,
+    /// e.g. `read`, `read-self`, `sender`, `inactive`; omitted for delivery receipts.
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(rename = "type")]
+    pub receipt_type: Option<String>,
+    /// Unix seconds for read receipts, defaults to now.
+    #[tsify(optional)]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+}
+
+struct Stanza<'a> {
+    node: &'a NodeRef<'static>,
+}
+
+impl<'a> Stanza<'a> {
+    fn expect(node: &'a NodeRef<'static>, tag: &str) -> Result<Self, JsValue> {
+        if &*node.tag != tag {
+            return Err(JsValue::from_str(&format!(
+                "Expected <{tag}> stanza, got <{}>",
+                &*node.tag
+            )));
+        }
+        Ok(Self { node })
+    }
+
+    fn attr(&self, key: &str) -> Option<String> {
+        self.node
+            .attrs
+            .as_slice()
+            .iter()
+            .find(|(k, _)| &**k == key)
+            .map(|(_, v)| v.as_str().to_string())
+    }
+
+    fn required(&self, key: &str) -> Result<String, JsValue> {
+        self.attr(key).ok_or_else(|| {
+            JsValue::from_str(&format!("<{}> stanza is missing `{key}`", &*self.node.tag))
+        })
+    }
+
+    fn number<T: std::str::FromStr>(&self, key: &str) -> Option<T> {
+        self.attr(key).and_then(|v| v.parse().ok())
+    }
+
+    fn jid(&self, key: &str) -> Option<ParsedJid> {
+        self.attr(key).and_then(|jid| {
+            let parsed = Jid::parse_str(&jid)?;
+            Some(ParsedJid {
+                user: parsed.user(),
+                server: parsed.server(),
+                domain_type: parsed.domain_type(),
+                device: parsed.device(),
+                jid,
+            })
+        })
+    }
+
+    fn required_jid(&self, key: &str) -> Result<ParsedJid, JsValue> {
+        self.jid(key).ok_or_else(|| {
+            JsValue::from_str(&format!(
+                "<{}> stanza has a missing or invalid `{key}`",
+                &*self.node.tag
+            ))
+        })
+    }
+
+    fn children(&self, tag: &'a str) -> impl Iterator<Item = Stanza<'a>> + 'a {
+        let children: &'a [NodeRef<'static>] = match self.node.content.as_deref() {
+            Some(NodeContentRef::Nodes(nodes)) => nodes,
+            _ => &[],
+        };
+        children
+            .iter()
+            .filter(move |child| &*child.tag == tag)
+            .map(|node| Stanza { node })
+    }
+
+    fn child(&self, tag: &'a str) -> Option<Stanza<'a>> {
+        self.children(tag).next()
+    }
+
+    fn bytes(&self) -> Vec<u8> {
+        match self.node.content.as_deref() {
+            Some(NodeContentRef::Bytes(bytes)) => bytes.to_vec(),
+            Some(NodeContentRef::String(s)) => s.as_bytes().to_vec(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+pub(crate) fn message_stanza(node: &NodeRef<'static>) -> Result<MessageStanza, JsValue> {
+    let stanza = Stanza::expect(node, "message")?;
+
+    let enc = stanza
+        .children("enc")
+        .map(|enc| EncChild {
+            enc_type: enc.attr("type").unwrap_or_default(),
+            v: enc.number("v"),
+            count: enc.number("count"),
+            mediatype: enc.attr("mediatype"),
+            ciphertext: enc.bytes(),
+        })
+        .collect();
+
+    Ok(MessageStanza {
+        id: stanza.required("id")?,
+        from: stanza.required_jid("from")?,
+        to: stanza.jid("to"),
+        message_type: stanza.required("type")?,
+        participant: stanza.jid("participant"),
+        recipient: stanza.jid("recipient"),
+        notify: stanza.attr("notify"),
+        t: stanza.number("t"),
+        offline: stanza.attr("offline").is_some(),
+        enc,
+    })
+}
+
+#[wasm_bindgen(js_name = parseMessageStanza)]
+pub fn parse_message_stanza(node: &InternalBinaryNode) -> Result<MessageStanza, JsValue> {
+    message_stanza(&node.current_node_ref()?)
+}
+
+#[wasm_bindgen(js_name = parseReceiptStanza)]
+pub fn parse_receipt_stanza(node: &InternalBinaryNode) -> Result<ReceiptStanza, JsValue> {
+    let node = node.current_node_ref()?;
+    let stanza = Stanza::expect(&node, "receipt")?;
+
+    let id = stanza.required("id")?;
+    let mut message_ids = vec![id.clone()];
+    if let Some(list) = stanza.child("list") {
+        message_ids.extend(list.children("item").filter_map(|item| item.attr("id")));
+    }
+
+    Ok(ReceiptStanza {
+        id,
+        message_ids,
+        from: stanza.required_jid("from")?,
+        receipt_type: stanza.attr("type"),
+        participant: stanza.jid("participant"),
+        recipient: stanza.jid("recipient"),
+        t: stanza.number("t"),
+        offline: stanza.attr("offline").is_some(),
+    })
+}
+
+#[wasm_bindgen(js_name = parseIqResult)]
+pub fn parse_iq_result(node: &InternalBinaryNode) -> Result<IqResult, JsValue> {
+    let node = node.current_node_ref()?;
+    let stanza = Stanza::expect(&node, "iq")?;
+
+    let error = stanza.child("error").map(|error| IqError {
+        code: error.number("code").unwrap_or(0),
+        text: error.attr("text"),
+    });
+
+    Ok(IqResult {
+        id: stanza.required("id")?,
+        from: stanza.jid("from"),
+        iq_type: stanza.required("type")?,
+        xmlns: stanza.attr("xmlns"),
+        error,
+    })
+}
+
+#[wasm_bindgen(js_name = parseNotification)]
+pub fn parse_notification(node: &InternalBinaryNode) -> Result<NotificationStanza, JsValue> {
+    let node = node.current_node_ref()?;
+    let stanza = Stanza::expect(&node, "notification")?;
+
+    Ok(NotificationStanza {
+        id: stanza.required("id")?,
+        from: stanza.required_jid("from")?,
+        notification_type: stanza.required("type")?,
+        participant: stanza.jid("participant"),
+        notify: stanza.attr("notify"),
+        t: stanza.number("t"),
+        offline: stanza.attr("offline").is_some(),
+    })
+}
+
+#[wasm_bindgen(js_name = parseAck)]
+pub fn parse_ack(node: &InternalBinaryNode) -> Result<AckStanza, JsValue> {
+    let node = node.current_node_ref()?;
+    let stanza = Stanza::expect(&node, "ack")?;
+
+    Ok(AckStanza {
+        id: stanza.required("id")?,
+        class: stanza.required("class")?,
+        from: stanza.required_jid("from")?,
+        ack_type: stanza.attr("type"),
+        participant: stanza.jid("participant"),
+        t: stanza.number("t"),
+        error: stanza.number("error"),
+    })
+}
+
+/// Builds the `<ack>` for a received stanza, with the same rules as Baileys' `sendMessageAck`.
+#[wasm_bindgen(js_name = buildAck)]
+pub fn build_ack(
+    node: &InternalBinaryNode,
+    options: Option<AckOptions>,
+) -> Result<BinaryNodeBuilder, JsValue> {
+    let options = options.unwrap_or_default();
+    let error_code = options.error_code.unwrap_or(0);
+    let node = node.current_node_ref()?;
+    let stanza = Stanza { node: &node };
+    let tag = &*node.tag;
+
+    let mut ack = BinaryNodeBuilder::new("ack".to_string())?;
+    ack.set_attr("id".to_string(), stanza.required("id")?);
+    ack.set_attr("to".to_string(), stanza.required("from")?);
+    ack.set_attr("class".to_string(), tag.to_string());
+
+    if error_code != 0 {
+        ack.set_attr("error".to_string(), error_code.to_string());
+    }
+    for key in ["participant", "recipient"] {
+        if let Some(value) = stanza.attr(key) {
+            ack.set_attr(key.to_string(), value);
+        }
+    }
+
+    let unavailable = tag == "message" && stanza.child("unavailable").is_some();
+    if let Some(ack_type) = stanza.attr("type")
+        && (tag != "message" || unavailable || error_code != 0)
+    {
+        ack.set_attr("type".to_string(), ack_type);
+    }
+    if unavailable && let Some(me_id) = options.me_id {
+        ack.set_attr("from".to_string(), me_id);
+    }
+
+    Ok(ack)
+}
+
+/// Builds a `<receipt>` for one or more messages, like Baileys' `sendReceipt`.
+#[wasm_bindgen(js_name = buildReceipt)]
+pub fn build_receipt(options: ReceiptOptions) -> Result<BinaryNodeBuilder, JsValue> {
+    let (first, rest) = options
+        .message_ids
+        .split_first()
+        .ok_or_else(|| JsValue::from_str("messageIds must not be empty"))?;
+
+    let mut receipt = BinaryNodeBuilder::new("receipt".to_string())?;
+    receipt.set_attr("id".to_string(), first.clone());
+
+    let receipt_type = options.receipt_type.as_deref();
+    if matches!(receipt_type, Some("read" | "read-self")) {
+        let t = options
+            .timestamp
+            .unwrap_or_else(|| (js_sys::Date::now() / 1000.0) as u64);
+        receipt.set_attr("t".to_string(), t.to_string());
+    }
+
+    let is_user_jid = options.jid.ends_with(&format!("@{DEFAULT_USER_SERVER}"));
+    match (receipt_type, options.participant) {
+        (Some("sender"), Some(participant)) if is_user_jid => {
+            receipt.set_attr("recipient".to_string(), options.jid);
+            receipt.set_attr("to".to_string(), participant);
+        }
+        (_, participant) => {
+            receipt.set_attr("to".to_string(), options.jid);
+            if let Some(participant) = participant {
+                receipt.set_attr("participant".to_string(), participant);
+            }
+        }
+    }
+
+    if let Some(receipt_type) = receipt_type {
+        receipt.set_attr("type".to_string(), receipt_type.to_string());
+    }
+
+    if !rest.is_empty() {
+        let mut list = BinaryNodeBuilder::new("list".to_string())?;
+        for id in rest {
+            let mut item = BinaryNodeBuilder::new("item".to_string())?;
+            item.set_attr("id".to_string(), id.clone());
+            list.append_child(&item)?;
+        }
+        receipt.append_child(&list)?;
+    }
+
+    Ok(receipt)
+}
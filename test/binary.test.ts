@@ -1,5 +1,11 @@
 import { describe, test, expect } from "bun:test";
-import { encodeNode, decodeNode, type BinaryNode } from "../dist";
+import {
+  encodeNode,
+  decodeNode,
+  encodeNodes,
+  decodeNodes,
+  type BinaryNode,
+} from "../dist";
 
 function arraysEqual(a: Uint8Array, b: Uint8Array): boolean {
   if (a.length !== b.length) return false;
@@ -342,3 +348,191 @@ test("should skip empty string values in attrs during encoding", () => {
   expect(attrs.whitespace).toBeUndefined();
   expect(attrs.valid).toBe("ok");
 });
+
+describe("InternalBinaryNode queries", () => {
+  const usync: BinaryNode = {
+    tag: "iq",
+    attrs: { id: "1", type: "result" },
+    content: [
+      {
+        tag: "usync",
+        attrs: {},
+        content: [
+          {
+            tag: "list",
+            attrs: {},
+            content: [
+              { tag: "user", attrs: { jid: "1@s.whatsapp.net" } },
+              { tag: "user", attrs: { jid: "2@s.whatsapp.net" } },
+              { tag: "other", attrs: {} },
+            ],
+          },
+        ],
+      },
+    ],
+  };
+
+  test("should look up attrs and children", () => {
+    const node = decodeNode(encodeNode(usync));
+
+    expect(node.getAttr("type")).toBe("result");
+    expect(node.getAttr("missing")).toBeUndefined();
+    expect(node.hasChild("usync")).toBe(true);
+    expect(node.hasChild("list")).toBe(false);
+    expect(node.getChild("usync")?.tag).toBe("usync");
+    expect(node.getChild("nope")).toBeUndefined();
+
+    const list = node.getChild("usync")!.getChild("list")!;
+    expect(list.getChildren("user").map((u) => u.attrs.jid)).toEqual([
+      "1@s.whatsapp.net",
+      "2@s.whatsapp.net",
+    ]);
+  });
+
+  test("should look up children without building the content array", () => {
+    const node = decodeNode(encodeNode(usync));
+    const RealArray = globalThis.Array;
+    let built = 0;
+    globalThis.Array = new Proxy(RealArray, {
+      construct(target, args, newTarget) {
+        built++;
+        return Reflect.construct(target, args, newTarget);
+      },
+    });
+    try {
+      node.getChild("usync")!.getChild("list")!.getChild("user");
+      node.hasChild("usync");
+    } finally {
+      globalThis.Array = RealArray;
+    }
+    expect(built).toBe(0);
+
+    // children looked up first are the ones `content` holds later
+    const usyncChild = node.getChild("usync")!;
+    expect((node.content as unknown[])[0]).toBe(usyncChild);
+  });
+
+  test("should select nodes by tag path", () => {
+    const node = decodeNode(encodeNode(usync));
+
+    const users = node.select("iq>usync>list>user");
+    expect(users.map((u) => u.attrs.jid)).toEqual([
+      "1@s.whatsapp.net",
+      "2@s.whatsapp.net",
+    ]);
+    expect(node.select("message>usync")).toEqual([]);
+    expect(node.select("iq")).toHaveLength(1);
+  });
+
+  test("should share children with content so edits are encoded", () => {
+    const node = decodeNode(encodeNode(usync));
+    const usyncChild = node.getChild("usync")!;
+    expect(usyncChild).toBe((node.content as unknown[])[0] as typeof usyncChild);
+    expect(node.select("iq>usync")[0]).toBe(usyncChild);
+
+    const [first] = usyncChild.getChild("list")!.getChildren("user");
+    first!.attrs = { jid: "3@s.whatsapp.net" };
+
+    const reencoded = decodeNode(encodeNode(node));
+    expect(
+      reencoded.select("iq>usync>list>user").map((u) => u.getAttr("jid")),
+    ).toEqual(["3@s.whatsapp.net", "2@s.whatsapp.net"]);
+  });
+
+  test("should honour content and attrs replaced from JS", () => {
+    const node = decodeNode(encodeNode(usync));
+    node.attrs = { type: "error" };
+    node.content = [{ tag: "error", attrs: { code: "404" } }];
+
+    expect(node.getAttr("type")).toBe("error");
+    expect(node.getChild("usync")).toBeUndefined();
+    expect(node.getChild("error")?.attrs.code).toBe("404");
+    expect(node.select("iq>error")).toHaveLength(1);
+  });
+});
+
+describe("encodeNode strict mode", () => {
+  test("should report every dropped attribute with its path", () => {
+    const node = {
+      tag: "iq",
+      attrs: { id: "1", type: "" },
+      content: [{ tag: "query", attrs: { to: undefined, empty: "  " } }],
+    } as unknown as BinaryNode;
+
+    // Lenient mode keeps the historical behaviour.
+    expect(decodeNode(encodeNode(node)).attrs).toEqual({ id: "1" });
+
+    let message = "";
+    try {
+      encodeNode(node, { strict: true });
+    } catch (err) {
+      message = String(err);
+    }
+    expect(message).toContain("iq.attrs.type: empty string");
+    expect(message).toContain("iq.content[0].attrs.to: unsupported value (undefined)");
+    expect(message).toContain("iq.content[0].attrs.empty: empty string");
+  });
+
+  test("should report unsupported content in strict mode", () => {
+    const node = { tag: "iq", attrs: {}, content: 42 } as unknown as BinaryNode;
+    expect(() => encodeNode(node)).toThrow("Invalid content type at iq.content");
+    expect(() => encodeNode(node, { strict: true })).toThrow(
+      "iq.content: unsupported content (number)",
+    );
+  });
+
+  test("should reject non-object children in every mode", () => {
+    const node = {
+      tag: "iq",
+      attrs: {},
+      content: [{ tag: "a", attrs: {} }, undefined],
+    } as unknown as BinaryNode;
+    expect(() => encodeNode(node)).toThrow(
+      "iq.content[1]: expected a node object, got undefined",
+    );
+    expect(() => encodeNode(node, { strict: true })).toThrow("iq.content[1]");
+  });
+
+  test("should reject a missing tag", () => {
+    const node = { attrs: {} } as unknown as BinaryNode;
+    expect(() => encodeNode(node)).toThrow("node.tag: expected a non-empty string");
+  });
+});
+
+describe("batch encode/decode", () => {
+  const nodes: BinaryNode[] = [
+    { tag: "ack", attrs: { id: "1", class: "receipt" } },
+    { tag: "message", attrs: { id: "2" }, content: "hello" },
+    {
+      tag: "iq",
+      attrs: { id: "3" },
+      content: [{ tag: "ping", attrs: {} }],
+    },
+  ];
+
+  test("encodeNodes should match encodeNode per node", () => {
+    const { buffer, offsets } = encodeNodes(nodes);
+    expect(offsets).toHaveLength(nodes.length + 1);
+    expect(offsets[nodes.length]).toBe(buffer.length);
+
+    nodes.forEach((node, i) => {
+      expect(buffer.subarray(offsets[i], offsets[i + 1])).toEqual(encodeNode(node));
+    });
+  });
+
+  test("decodeNodes should decode every buffer", () => {
+    const decoded = decodeNodes(nodes.map((node) => encodeNode(node)));
+    expect(decoded.map((n) => n.tag)).toEqual(["ack", "message", "iq"]);
+    expect(decoded[0]!.attrs.class).toBe("receipt");
+    expect(decoded[2]!.getChild("ping")?.tag).toBe("ping");
+  });
+
+  test("should report the failing index", () => {
+    expect(() =>
+      encodeNodes([nodes[0]!, { attrs: {} } as unknown as BinaryNode]),
+    ).toThrow("nodes[1]");
+    expect(() => decodeNodes([encodeNode(nodes[0]!), new Uint8Array()])).toThrow(
+      "buffers[1]",
+    );
+  });
+});
@@ -0,0 +1,68 @@
+import { describe, expect, it } from "bun:test";
+import { inflateSync } from "node:zlib";
+import { decodeBinaryNode } from "baileys";
+import { decodeNode, encodeNode, encodeNodes, type BinaryNode } from "../dist";
+
+const large: BinaryNode = {
+  tag: "iq",
+  attrs: { id: "1", type: "set", xmlns: "w:sync:app:state" },
+  content: [
+    {
+      tag: "sync",
+      attrs: {},
+      content: Array.from({ length: 200 }, (_, i) => ({
+        tag: "collection",
+        attrs: { name: "regular_high", version: String(i) },
+        content: new TextEncoder().encode("patch-data ".repeat(8)),
+      })),
+    },
+  ],
+};
+
+const small: BinaryNode = { tag: "ping", attrs: { id: "2" } };
+
+describe("encodeNode compression", () => {
+  it("should deflate and set the flag byte with compress: true", () => {
+    const plain = encodeNode(large);
+    const packed = encodeNode(large, { compress: true });
+
+    expect(packed[0]).toBe(2);
+    expect(packed.length).toBeLessThan(plain.length / 2);
+    expect(new Uint8Array(inflateSync(packed.subarray(1)))).toEqual(
+      plain.subarray(1),
+    );
+  });
+
+  it("should round-trip through decodeNode and Baileys", async () => {
+    const packed = encodeNode(large, { compress: true });
+    expect(encodeNode(decodeNode(packed))).toEqual(encodeNode(large));
+
+    const legacy = await decodeBinaryNode(Buffer.from(packed));
+    expect(legacy.tag).toBe("iq");
+    expect(legacy.attrs.xmlns).toBe("w:sync:app:state");
+  });
+
+  it("should skip small or incompressible payloads in auto mode", () => {
+    expect(encodeNode(small, { compress: "auto" })).toEqual(encodeNode(small));
+    expect(encodeNode(large, { compress: "auto" })[0]).toBe(2);
+
+    const plain = encodeNode(large);
+    expect(
+      encodeNode(large, { compress: "auto", threshold: plain.length + 1 }),
+    ).toEqual(plain);
+
+    const noisy: BinaryNode = {
+      tag: "media",
+      attrs: {},
+      content: crypto.getRandomValues(new Uint8Array(4096)),
+    };
+    expect(encodeNode(noisy, { compress: "auto", threshold: 0 })[0]).toBe(0);
+  });
+
+  it("should apply to encodeNodes", () => {
+    const { buffer, offsets } = encodeNodes([large, small], { compress: true });
+    expect(buffer[offsets[0]!]).toBe(2);
+    expect(buffer[offsets[1]!]).toBe(2);
+    expect(decodeNode(buffer.subarray(offsets[1], offsets[2])).tag).toBe("ping");
+  });
+});
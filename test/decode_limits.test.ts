@@ -0,0 +1,93 @@
+import { describe, expect, it } from "bun:test";
+import { deflateSync } from "node:zlib";
+import { decodeNode, decodeNodes, encodeNode, type BinaryNode } from "../dist";
+
+function compressed(node: BinaryNode): Uint8Array {
+  const plain = encodeNode(node);
+  return new Uint8Array([0x02, ...deflateSync(plain.subarray(1))]);
+}
+
+function nested(depth: number): BinaryNode {
+  let node: BinaryNode = { tag: "leaf", attrs: {} };
+  for (let i = 1; i < depth; i++) {
+    node = { tag: "n", attrs: {}, content: [node] };
+  }
+  return node;
+}
+
+function catchError(fn: () => unknown): Error & { limit?: string } {
+  try {
+    fn();
+  } catch (err) {
+    return err as Error & { limit?: string };
+  }
+  throw new Error("expected a throw");
+}
+
+describe("decode limits", () => {
+  it("should stop inflating past maxInflatedBytes", () => {
+    const bomb = compressed({
+      tag: "message",
+      attrs: {},
+      content: new Uint8Array(1 << 20),
+    });
+    expect(bomb.length).toBeLessThan(4096);
+
+    const err = catchError(() => decodeNode(bomb, { maxInflatedBytes: 64 * 1024 }));
+    expect(err.name).toBe("DecodeLimitError");
+    expect(err.limit).toBe("maxInflatedBytes");
+
+    expect(decodeNode(bomb, { maxInflatedBytes: 2 << 20 }).tag).toBe("message");
+  });
+
+  it("should apply maxInflatedBytes to uncompressed frames", () => {
+    const frame = encodeNode({ tag: "a", attrs: {}, content: new Uint8Array(100) });
+    expect(catchError(() => decodeNode(frame, { maxInflatedBytes: 50 })).limit).toBe(
+      "maxInflatedBytes",
+    );
+  });
+
+  it("should enforce maxDepth", () => {
+    const frame = encodeNode(nested(10));
+    expect(decodeNode(frame, { maxDepth: 10 }).tag).toBe("n");
+    const err = catchError(() => decodeNode(frame, { maxDepth: 9 }));
+    expect(err.limit).toBe("maxDepth");
+    expect(err.message).toContain("maxDepth is 9");
+  });
+
+  it("should enforce maxChildren and maxAttributes", () => {
+    const frame = encodeNode({
+      tag: "list",
+      attrs: { a: "1", b: "2", c: "3" },
+      content: Array.from({ length: 5 }, () => ({ tag: "item", attrs: {} })),
+    });
+    expect(catchError(() => decodeNode(frame, { maxChildren: 4 })).limit).toBe(
+      "maxChildren",
+    );
+    expect(catchError(() => decodeNode(frame, { maxAttributes: 2 })).limit).toBe(
+      "maxAttributes",
+    );
+    expect(decodeNode(frame, { maxChildren: 5, maxAttributes: 3 }).tag).toBe("list");
+  });
+
+  it("should apply to decodeNodes", () => {
+    const ok = encodeNode({ tag: "a", attrs: {} });
+    const deep = encodeNode(nested(4));
+    const err = catchError(() => decodeNodes([ok, deep], { maxDepth: 3 }));
+    expect(err.name).toBe("DecodeLimitError");
+    expect(err.message).toStartWith("buffers[1]: ");
+  });
+
+  it("should reject deep nesting before parsing it", () => {
+    // <n><n>...<n/>...</n></n>, far deeper than the parser could recurse.
+    const levels = 200_000;
+    const open = [248, 2, 252, 1, 0x6e, 248, 1];
+    const frame = new Uint8Array(1 + levels * open.length + 5);
+    for (let i = 0; i < levels; i++) frame.set(open, 1 + i * open.length);
+    frame.set([248, 1, 252, 1, 0x6e], 1 + levels * open.length);
+
+    const err = catchError(() => decodeNode(frame, { maxDepth: 64 }));
+    expect(err.limit).toBe("maxDepth");
+    expect(err.message).toContain("maxDepth is 64");
+  });
+});
@@ -0,0 +1,43 @@
+import {
+  ProtocolAddress,
+  SessionBuilder,
+  generatePreKey,
+  generateSignedPreKey,
+} from "../../dist/index.js";
+import { FakeStorage } from "./fake_storage";
+
+/**
+ * Gives `sender` a session with a fresh `name.device` recipient by processing
+ * the recipient's prekey bundle. If `senderName` is set, the recipient also
+ * trusts `sender`'s identity under that name.
+ */
+export async function connect(
+  sender: FakeStorage,
+  name: string,
+  device: number,
+  senderName?: string,
+) {
+  const recipient = new FakeStorage();
+  sender.trustIdentity(name, recipient.ourIdentityKeyPair.pubKey);
+  if (senderName) {
+    recipient.trustIdentity(senderName, sender.ourIdentityKeyPair.pubKey);
+  }
+
+  const signedPreKey = generateSignedPreKey(recipient.ourIdentityKeyPair, 1);
+  const preKey = generatePreKey(100 + device);
+  recipient.storeSignedPreKey(signedPreKey.keyId, signedPreKey);
+  recipient.storePreKey(preKey.keyId, preKey.keyPair);
+
+  const address = new ProtocolAddress(name, device);
+  await new SessionBuilder(sender, address).processPreKeyBundle({
+    registrationId: recipient.ourRegistrationId,
+    identityKey: recipient.ourIdentityKeyPair.pubKey,
+    signedPreKey: {
+      keyId: signedPreKey.keyId,
+      publicKey: signedPreKey.keyPair.pubKey,
+      signature: signedPreKey.signature,
+    },
+    preKey: { keyId: preKey.keyId, publicKey: preKey.keyPair.pubKey },
+  });
+  return { address, storage: recipient };
+}
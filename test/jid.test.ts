@@ -0,0 +1,111 @@
+import { describe, expect, it } from "bun:test";
+import {
+  jidDecode as baileysJidDecode,
+  jidEncode as baileysJidEncode,
+} from "baileys";
+import { Jid, decodeNode, encodeNode, jidDecode, jidEncode } from "../dist";
+
+const SAMPLES = [
+  "5511999999999@s.whatsapp.net",
+  "5511999999999:12@s.whatsapp.net",
+  "123456789012345@lid",
+  "123456789012345:3@lid",
+  "5511999999999_1:2@s.whatsapp.net",
+  "120363025246125486@g.us",
+  "120363025246125486@newsletter",
+  "status@broadcast",
+  "5511999999999:99@hosted",
+  "123456789012345:99@hosted.lid",
+  "@s.whatsapp.net",
+];
+
+describe("jidDecode / jidEncode", () => {
+  it("should decode like Baileys", () => {
+    for (const jid of SAMPLES) {
+      expect(jidDecode(jid)).toEqual(
+        JSON.parse(JSON.stringify(baileysJidDecode(jid))),
+      );
+    }
+    expect(jidDecode("not-a-jid")).toBeUndefined();
+  });
+
+  it("should coerce non-numeric devices and agents to NaN like Baileys", () => {
+    for (const jid of ["1:x@s.whatsapp.net", "1_x:2@s.whatsapp.net"]) {
+      const decoded = jidDecode(jid)!;
+      const expected = baileysJidDecode(jid)!;
+      expect(decoded.user).toBe(expected.user);
+      expect(decoded.domainType).toEqual(expected.domainType);
+      expect(decoded.device).toEqual(expected.device);
+    }
+    expect(jidDecode("1:x@s.whatsapp.net")!.device).toBeNaN();
+    expect(() => Jid.parse("1:x@s.whatsapp.net")).toThrow("Invalid JID");
+  });
+
+  it("should encode like Baileys", () => {
+    expect(jidEncode("5511", "s.whatsapp.net", 3)).toBe(
+      baileysJidEncode("5511", "s.whatsapp.net", 3),
+    );
+    expect(jidEncode("5511", "s.whatsapp.net", 0)).toBe(
+      baileysJidEncode("5511", "s.whatsapp.net", 0),
+    );
+    expect(jidEncode(undefined, "s.whatsapp.net")).toBe("@s.whatsapp.net");
+  });
+});
+
+describe("Jid", () => {
+  it("should round-trip through parse and toString", () => {
+    for (const jid of SAMPLES) {
+      expect(Jid.parse(jid).toString()).toBe(jid);
+    }
+  });
+
+  it("should classify servers", () => {
+    expect(Jid.parse("1@lid").isLid()).toBe(true);
+    expect(Jid.parse("1@g.us").isGroup()).toBe(true);
+    expect(Jid.parse("1@newsletter").isNewsletter()).toBe(true);
+    expect(Jid.parse("status@broadcast").isBroadcast()).toBe(true);
+    expect(Jid.parse("1@s.whatsapp.net").isGroup()).toBe(false);
+  });
+
+  it("should build signal addresses with the domain suffix", () => {
+    expect(Jid.parse("5511:2@s.whatsapp.net").toSignalAddressString()).toBe(
+      "5511.2",
+    );
+    expect(Jid.parse("1234@lid").toSignalAddressString()).toBe("1234_1.0");
+    expect(Jid.parse("1234:99@hosted.lid").toSignalAddressString()).toBe(
+      "1234_129.99",
+    );
+
+    const address = Jid.parse("1234:5@lid").toProtocolAddress();
+    expect(address.id).toBe("1234_1");
+    expect(address.deviceId).toBe(5);
+
+    expect(() =>
+      Jid.parse("5511:99@s.whatsapp.net").toSignalAddressString(),
+    ).toThrow("device 99");
+  });
+
+  it("should reject malformed input", () => {
+    expect(() => Jid.parse("no-server")).toThrow("Invalid JID");
+    expect(() => Jid.parse("1:x@s.whatsapp.net")).toThrow("Invalid JID");
+  });
+
+  it("should be accepted as an attribute value by encodeNode", () => {
+    const jid = Jid.parse("5511999999999:12@s.whatsapp.net");
+    const withJid = encodeNode({ tag: "iq", attrs: { to: jid } });
+    const withString = encodeNode({ tag: "iq", attrs: { to: jid.toString() } });
+
+    expect(withJid).toEqual(withString);
+    expect(decodeNode(withJid).attrs.to).toBe(jid.toString());
+  });
+
+  it("should not treat look-alike objects as a Jid", () => {
+    const fake = { toString: () => "5511@s.whatsapp.net" };
+    expect(() =>
+      encodeNode(
+        { tag: "iq", attrs: { to: fake as unknown as string } },
+        { strict: true },
+      ),
+    ).toThrow("unsupported value");
+  });
+});
@@ -0,0 +1,205 @@
+import { describe, it, expect } from "bun:test";
+import {
+  createCipheriv,
+  createDecipheriv,
+  createHash,
+  createHmac,
+  randomBytes,
+} from "crypto";
+import {
+  MediaDecryptor,
+  MediaEncryptor,
+  decryptMedia,
+  encryptMedia,
+  getMediaKeys,
+} from "../dist";
+import { getMediaKeys as baileysGetMediaKeys } from "baileys/lib/Utils/messages-media";
+
+function hex(buffer: Uint8Array | Buffer): string {
+  return Buffer.from(buffer).toString("hex");
+}
+
+async function referenceEncrypt(
+  plaintext: Buffer,
+  mediaKey: Buffer,
+  mediaType: "image" | "video" | "audio" | "document",
+) {
+  const { iv, cipherKey, macKey } = await baileysGetMediaKeys(
+    mediaKey,
+    mediaType,
+  );
+  const aes = createCipheriv("aes-256-cbc", cipherKey, iv);
+  const ciphertext = Buffer.concat([aes.update(plaintext), aes.final()]);
+  const mac = createHmac("sha256", macKey!)
+    .update(iv)
+    .update(ciphertext)
+    .digest()
+    .subarray(0, 10);
+  const enc = Buffer.concat([ciphertext, mac]);
+
+  return {
+    enc,
+    fileSha256: createHash("sha256").update(plaintext).digest(),
+    fileEncSha256: createHash("sha256").update(enc).digest(),
+  };
+}
+
+function chunked(data: Uint8Array, size: number): Uint8Array[] {
+  const chunks: Uint8Array[] = [];
+  for (let i = 0; i < data.length; i += size) {
+    chunks.push(data.subarray(i, i + size));
+  }
+  return chunks;
+}
+
+function streamOf(chunks: Uint8Array[]): ReadableStream<Uint8Array> {
+  return new ReadableStream({
+    start(controller) {
+      for (const chunk of chunks) controller.enqueue(chunk);
+      controller.close();
+    },
+  });
+}
+
+describe("getMediaKeys", () => {
+  it("should expand keys identically to Baileys", async () => {
+    const mediaKey = randomBytes(32);
+
+    for (const type of ["image", "video", "audio", "document"] as const) {
+      const wasm = getMediaKeys(mediaKey, type);
+      const baileys = await baileysGetMediaKeys(mediaKey, type);
+
+      expect(hex(wasm.iv)).toBe(hex(baileys.iv));
+      expect(hex(wasm.cipherKey)).toBe(hex(baileys.cipherKey));
+      expect(hex(wasm.macKey)).toBe(hex(baileys.macKey!));
+    }
+  });
+
+  it("should reject unknown media types and bad key lengths", () => {
+    expect(() => getMediaKeys(randomBytes(32), "bogus")).toThrow(
+      "Unsupported media type",
+    );
+    expect(() => getMediaKeys(randomBytes(16), "image")).toThrow(
+      "mediaKey must be 32 bytes",
+    );
+  });
+});
+
+describe("MediaEncryptor", () => {
+  it("should match the reference encryption across uneven chunks", async () => {
+    const plaintext = randomBytes(100_003);
+    const encryptor = new MediaEncryptor("video");
+    const expected = await referenceEncrypt(
+      plaintext,
+      Buffer.from(encryptor.mediaKey),
+      "video",
+    );
+
+    const parts: Uint8Array[] = [];
+    for (const chunk of chunked(plaintext, 7_777)) {
+      parts.push(encryptor.update(chunk));
+    }
+    const result = encryptor.finalize();
+    parts.push(result.tail);
+
+    expect(hex(Buffer.concat(parts))).toBe(hex(expected.enc));
+    expect(hex(result.fileSha256)).toBe(hex(expected.fileSha256));
+    expect(hex(result.fileEncSha256)).toBe(hex(expected.fileEncSha256));
+    expect(hex(result.mac)).toBe(hex(expected.enc.subarray(-10)));
+    expect(result.fileLength).toBe(plaintext.length);
+  });
+
+  it("should pad block-aligned input with a full block", async () => {
+    const mediaKey = randomBytes(32);
+    const plaintext = randomBytes(64);
+    const encryptor = new MediaEncryptor("image", mediaKey);
+
+    const head = encryptor.update(plaintext);
+    const { tail } = encryptor.finalize();
+
+    expect(head.length).toBe(64);
+    expect(tail.length).toBe(16 + 10);
+  });
+
+  it("should refuse to be used after finalize", () => {
+    const encryptor = new MediaEncryptor("audio");
+    encryptor.finalize();
+    expect(() => encryptor.update(new Uint8Array(1))).toThrow(
+      "already finalized",
+    );
+  });
+});
+
+describe("MediaDecryptor", () => {
+  it("should decrypt reference ciphertext fed in small chunks", async () => {
+    const mediaKey = randomBytes(32);
+    const plaintext = randomBytes(50_000);
+    const { enc } = await referenceEncrypt(plaintext, mediaKey, "document");
+
+    const decryptor = new MediaDecryptor(mediaKey, "document");
+    const parts: Uint8Array[] = [];
+    for (const chunk of chunked(enc, 1_000)) {
+      parts.push(decryptor.update(chunk));
+    }
+    parts.push(decryptor.finalize());
+
+    expect(hex(Buffer.concat(parts))).toBe(hex(plaintext));
+  });
+
+  it("should reject a tampered MAC", async () => {
+    const mediaKey = randomBytes(32);
+    const { enc } = await referenceEncrypt(randomBytes(300), mediaKey, "image");
+    enc[enc.length - 1] ^= 0xff;
+
+    const decryptor = new MediaDecryptor(mediaKey, "image");
+    decryptor.update(enc);
+    expect(() => decryptor.finalize()).toThrow("Media MAC mismatch");
+  });
+
+  it("should reject truncated ciphertext", () => {
+    const decryptor = new MediaDecryptor(randomBytes(32), "image");
+    decryptor.update(new Uint8Array(20));
+    expect(() => decryptor.finalize()).toThrow("Invalid media ciphertext length");
+  });
+});
+
+describe("encryptMedia / decryptMedia", () => {
+  it("should round-trip a ReadableStream", async () => {
+    const plaintext = randomBytes(200_000);
+
+    const encrypted = await encryptMedia(
+      streamOf(chunked(plaintext, 65_536)),
+      "audio",
+    );
+    const expected = await referenceEncrypt(
+      plaintext,
+      Buffer.from(encrypted.mediaKey),
+      "audio",
+    );
+    expect(hex(encrypted.ciphertext)).toBe(hex(expected.enc));
+
+    const decrypted = await decryptMedia(
+      streamOf(chunked(encrypted.ciphertext, 10_000)),
+      encrypted.mediaKey,
+      "audio",
+    );
+    expect(hex(decrypted)).toBe(hex(plaintext));
+  });
+
+  it("should decrypt data produced by node crypto", async () => {
+    const mediaKey = randomBytes(32);
+    const plaintext = Buffer.from("hello media");
+    const { enc } = await referenceEncrypt(plaintext, mediaKey, "image");
+
+    const decrypted = await decryptMedia(enc, mediaKey, "image");
+    expect(Buffer.from(decrypted).toString()).toBe("hello media");
+
+    const { iv, cipherKey } = await baileysGetMediaKeys(mediaKey, "image");
+    const aes = createDecipheriv("aes-256-cbc", cipherKey, iv);
+    const viaNode = Buffer.concat([
+      aes.update(enc.subarray(0, -10)),
+      aes.final(),
+    ]);
+    expect(hex(decrypted)).toBe(hex(viaNode));
+  });
+});
@@ -0,0 +1,75 @@
+import { describe, it, expect } from "bun:test";
+import { createHmac, randomBytes } from "crypto";
+import {
+  MediaEncryptor,
+  MediaSidecarGenerator,
+  encryptMedia,
+  verifyMediaSidecar,
+} from "../dist";
+import { getMediaKeys as baileysGetMediaKeys } from "baileys/lib/Utils/messages-media";
+
+const CHUNK = 64 * 1024;
+
+function hex(buffer: Uint8Array | Buffer): string {
+  return Buffer.from(buffer).toString("hex");
+}
+
+// Mirrors the sidecar loop in Baileys' `encryptedStream`.
+async function referenceSidecar(enc: Uint8Array, mediaKey: Uint8Array) {
+  const { iv, macKey } = await baileysGetMediaKeys(mediaKey, "video");
+  const stream = Buffer.concat([iv, enc]);
+  const parts: Buffer[] = [];
+  for (let i = 0; i < stream.length; i += CHUNK) {
+    const window = stream.subarray(i, i + CHUNK + 16);
+    parts.push(createHmac("sha256", macKey!).update(window).digest().subarray(0, 10));
+  }
+  return Buffer.concat(parts);
+}
+
+describe("streaming sidecar", () => {
+  it("should match the reference sidecar when encrypting", async () => {
+    const plaintext = randomBytes(3 * CHUNK + 1_234);
+    const encrypted = await encryptMedia(plaintext, "video", undefined, true);
+
+    const expected = await referenceSidecar(encrypted.ciphertext, encrypted.mediaKey);
+    expect(hex(encrypted.streamingSidecar!)).toBe(hex(expected));
+  });
+
+  it("should be omitted unless requested", async () => {
+    const encrypted = await encryptMedia(randomBytes(100), "video");
+    expect(encrypted.streamingSidecar).toBeUndefined();
+  });
+
+  it("should be identical from the encryptor and the standalone generator", () => {
+    const plaintext = randomBytes(2 * CHUNK + 77);
+    const encryptor = new MediaEncryptor("video", undefined, true);
+    const generator = new MediaSidecarGenerator(encryptor.mediaKey, "video");
+
+    for (let i = 0; i < plaintext.length; i += 10_000) {
+      generator.update(encryptor.update(plaintext.subarray(i, i + 10_000)));
+    }
+    const result = encryptor.finalize();
+    generator.update(result.tail);
+
+    expect(hex(generator.finalize())).toBe(hex(result.streamingSidecar!));
+  });
+
+  it("should verify aligned ranges and reject tampered ones", async () => {
+    const encrypted = await encryptMedia(randomBytes(4 * CHUNK), "video", undefined, true);
+    const { ciphertext, mediaKey, streamingSidecar } = encrypted;
+    const sidecar = streamingSidecar!;
+
+    expect(verifyMediaSidecar(mediaKey, "video", sidecar, ciphertext, 0)).toBe(true);
+
+    const offset = CHUNK - 16;
+    const range = ciphertext.slice(offset, offset + CHUNK + 16);
+    expect(verifyMediaSidecar(mediaKey, "video", sidecar, range, offset)).toBe(true);
+
+    range[100] ^= 0xff;
+    expect(verifyMediaSidecar(mediaKey, "video", sidecar, range, offset)).toBe(false);
+
+    expect(() =>
+      verifyMediaSidecar(mediaKey, "video", sidecar, range, CHUNK),
+    ).toThrow("rangeOffset");
+  });
+});
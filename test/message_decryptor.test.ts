@@ -0,0 +1,94 @@
+import { describe, expect, it } from "bun:test";
+import {
+  MessageDecryptor,
+  SessionCipher,
+  decodeNode,
+  encodeMessage,
+  encodeNode,
+  unpadMessage,
+} from "../dist";
+import { FakeStorage } from "./helpers/fake_storage";
+import { connect } from "./helpers/signal_session";
+
+const pad = (bytes: Uint8Array, n = 5) =>
+  new Uint8Array([...bytes, ...new Array(n).fill(n)]);
+
+async function setupSession() {
+  const aliceStorage = new FakeStorage();
+  const bob = await connect(aliceStorage, "bob", 0, "alice");
+  return {
+    alice: new SessionCipher(aliceStorage, bob.address),
+    bobStorage: bob.storage,
+  };
+}
+
+describe("MessageDecryptor", () => {
+  it("should decrypt, unpad and decode a pkmsg stanza", async () => {
+    const { alice, bobStorage } = await setupSession();
+    const encrypted = await alice.encrypt(pad(encodeMessage({ conversation: "hi bob" })));
+    expect(encrypted.type).toBe(3);
+
+    const stanza = decodeNode(
+      encodeNode({
+        tag: "message",
+        attrs: { id: "ABC", from: "alice@s.whatsapp.net", type: "text" },
+        content: [{ tag: "enc", attrs: { v: "2", type: "pkmsg" }, content: encrypted.body }],
+      }),
+    );
+
+    const result = await new MessageDecryptor(bobStorage).decrypt(stanza);
+    expect(result.id).toBe("ABC");
+    expect(result.author).toBe("alice@s.whatsapp.net");
+    expect(result.results).toHaveLength(1);
+    expect(result.results[0]!.error).toBeUndefined();
+    expect(result.results[0]!.message?.conversation).toBe("hi bob");
+  });
+
+  it("should unpad leniently like Baileys", async () => {
+    const { alice, bobStorage } = await setupSession();
+    // Only the last byte holds the pad length; unpadMessage rejects this.
+    const plaintext = new Uint8Array([...encodeMessage({ conversation: "loose" }), 7, 1, 3]);
+    expect(() => unpadMessage(plaintext)).toThrow();
+    const encrypted = await alice.encrypt(plaintext);
+
+    const result = await new MessageDecryptor(bobStorage).decrypt({
+      tag: "message",
+      attrs: { id: "L", from: "alice@s.whatsapp.net", type: "text" },
+      content: [{ tag: "enc", attrs: { v: "2", type: "pkmsg" }, content: encrypted.body }],
+    });
+    expect(result.results[0]!.error).toBeUndefined();
+    expect(result.results[0]!.message?.conversation).toBe("loose");
+  });
+
+  it("should report failures per enc child", async () => {
+    const { bobStorage } = await setupSession();
+    const result = await new MessageDecryptor(bobStorage).decrypt({
+      tag: "message",
+      attrs: { id: "X", from: "120363@g.us", participant: "alice@s.whatsapp.net", type: "text" },
+      content: [
+        { tag: "enc", attrs: { v: "2", type: "msg" }, content: new Uint8Array([1, 2, 3]) },
+        { tag: "enc", attrs: { v: "2", type: "weird" }, content: new Uint8Array([1]) },
+        { tag: "enc", attrs: { v: "2", type: "msmsg" }, content: new Uint8Array([1]) },
+        {
+          tag: "enc",
+          attrs: { type: "plaintext" },
+          content: encodeMessage({ conversation: "public" }),
+        },
+      ],
+    });
+
+    expect(result.chat).toBe("120363@g.us");
+    expect(result.author).toBe("alice@s.whatsapp.net");
+    expect(result.results[0]!.error).toContain("Invalid WhisperMessage format");
+    expect(result.results[1]!.error).toContain('Unknown enc type "weird"');
+    expect(result.results[2]!.error).toBe("unsupported: msmsg");
+    expect(result.results[3]!.message?.conversation).toBe("public");
+  });
+
+  it("should reject stanzas that are not messages", async () => {
+    const decryptor = new MessageDecryptor(new FakeStorage());
+    await expect(
+      decryptor.decrypt({ tag: "receipt", attrs: { id: "1" } }),
+    ).rejects.toThrow("Expected <message> stanza");
+  });
+});
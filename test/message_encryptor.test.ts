@@ -0,0 +1,100 @@
+import { describe, expect, it } from "bun:test";
+import {
+  MessageDecryptor,
+  MessageEncryptor,
+  ProtocolAddress,
+  decodeNode,
+  encodeMessage,
+  encodeNode,
+} from "../dist";
+import { FakeStorage } from "./helpers/fake_storage";
+import { connect } from "./helpers/signal_session";
+
+describe("MessageEncryptor.encryptForDevices", () => {
+  it("should build a participants node and list missing sessions", async () => {
+    const alice = new FakeStorage();
+    const phone = await connect(alice, "bob", 0);
+    const desktop = await connect(alice, "bob", 2);
+    const plaintext = encodeMessage({ conversation: "fan out" });
+
+    const result = await MessageEncryptor.encryptForDevices(
+      plaintext,
+      [phone.address, desktop.address.toString(), "bob_1.5"],
+      alice,
+    );
+
+    expect(result.missingSessions).toEqual(["bob:5@lid"]);
+    expect(result.failures).toEqual([]);
+    expect(result.shouldIncludeDeviceIdentity).toBe(true);
+
+    const participants = decodeNode(encodeNode(result.participants));
+    expect(participants.tag).toBe("participants");
+    const to = participants.getChildren("to");
+    expect(to.map((node) => node.attrs.jid)).toEqual([
+      "bob@s.whatsapp.net",
+      "bob:2@s.whatsapp.net",
+    ]);
+    expect(to[0]!.getChild("enc")!.attrs).toEqual({ v: "2", type: "pkmsg" });
+
+    // Every device receives the same padded payload.
+    for (const [index, device] of [phone, desktop].entries()) {
+      device.storage.trustIdentity("alice", alice.ourIdentityKeyPair.pubKey);
+      const decrypted = await new MessageDecryptor(device.storage).decrypt({
+        tag: "message",
+        attrs: { id: "1", from: "alice@s.whatsapp.net", type: "text" },
+        content: [to[index]!.getChild("enc")!],
+      });
+      expect(decrypted.results[0]!.message?.conversation).toBe("fan out");
+    }
+  });
+
+  it("should report failing devices and still encrypt for the others", async () => {
+    const alice = new FakeStorage();
+    const bob = await connect(alice, "bob", 0);
+    const carol = await connect(alice, "carol", 0);
+    alice.trustIdentity("carol", new FakeStorage().ourIdentityKeyPair.pubKey);
+    const carolSession = alice.getSession(carol.address.toString());
+
+    const result = await MessageEncryptor.encryptForDevices(
+      encodeMessage({ conversation: "partial" }),
+      [carol.address, bob.address],
+      alice,
+    );
+
+    expect(result.failures.map((failure) => failure.jid)).toEqual(["carol@s.whatsapp.net"]);
+    expect(result.failures[0]!.error).not.toBe("");
+    expect(alice.getSession(carol.address.toString())).toEqual(carolSession);
+
+    const to = decodeNode(encodeNode(result.participants)).getChildren("to");
+    expect(to.map((node) => node.attrs.jid)).toEqual(["bob@s.whatsapp.net"]);
+    bob.storage.trustIdentity("alice", alice.ourIdentityKeyPair.pubKey);
+    const decrypted = await new MessageDecryptor(bob.storage).decrypt({
+      tag: "message",
+      attrs: { id: "2", from: "alice@s.whatsapp.net", type: "text" },
+      content: [to[0]!.getChild("enc")!],
+    });
+    expect(decrypted.results[0]!.message?.conversation).toBe("partial");
+  });
+
+  it("should reject malformed addresses", async () => {
+    await expect(
+      MessageEncryptor.encryptForDevices(new Uint8Array([1]), ["nodevice"], new FakeStorage()),
+    ).rejects.toThrow("addresses[0]");
+    await expect(
+      MessageEncryptor.encryptForDevices(
+        new Uint8Array([1]),
+        ["bob.0", "nodevice"],
+        new FakeStorage(),
+      ),
+    ).rejects.toThrow("addresses[1]");
+    // only real ProtocolAddress instances are read as objects
+    const lookalike = { toString: () => "bob.1" };
+    await expect(
+      MessageEncryptor.encryptForDevices(
+        new Uint8Array([1]),
+        [lookalike as unknown as string],
+        new FakeStorage(),
+      ),
+    ).rejects.toThrow("addresses[0]");
+  });
+});
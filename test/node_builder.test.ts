@@ -0,0 +1,152 @@
+import { describe, expect, it } from "bun:test";
+import {
+  BinaryNodeBuilder,
+  NoiseSession,
+  decodeNode,
+  encodeNode,
+  encodeNodes,
+  type BinaryNode,
+} from "../dist";
+
+const reference: BinaryNode = {
+  tag: "iq",
+  attrs: { id: "1", to: "s.whatsapp.net", type: "get" },
+  content: [
+    { tag: "ping", attrs: {} },
+    { tag: "enc", attrs: { v: "2" }, content: new Uint8Array([1, 2, 3]) },
+    { tag: "body", attrs: {}, content: "hi" },
+  ],
+};
+
+function buildReference(): BinaryNodeBuilder {
+  const iq = new BinaryNodeBuilder("iq");
+  iq.setAttr("id", "1");
+  iq.setAttr("to", "s.whatsapp.net");
+  iq.setAttr("type", "set");
+  iq.setAttr("type", "get");
+
+  iq.appendChild(new BinaryNodeBuilder("ping"));
+  const enc = new BinaryNodeBuilder("enc");
+  enc.setAttr("v", "2");
+  enc.setBytes(new Uint8Array([1, 2, 3]));
+  iq.appendChild(enc);
+  iq.appendNode({ tag: "body", attrs: {}, content: "hi" });
+  return iq;
+}
+
+describe("BinaryNodeBuilder", () => {
+  it("should encode like the equivalent BinaryNode", () => {
+    const expected = encodeNode(reference);
+    const builder = buildReference();
+
+    expect(builder.encode()).toEqual(expected);
+    expect(encodeNode(builder)).toEqual(expected);
+    expect(encodeNodes([builder, reference]).buffer).toEqual(
+      new Uint8Array([...expected, ...expected]),
+    );
+  });
+
+  it("should be accepted as a child of a plain node", () => {
+    const child = new BinaryNodeBuilder("ping");
+    child.setAttr("a", "b");
+    const bytes = encodeNode({ tag: "iq", attrs: {}, content: [child] });
+    expect(decodeNode(bytes).getChild("ping")?.attrs.a).toBe("b");
+  });
+
+  it("should remove attributes and reject mixed content", () => {
+    const node = new BinaryNodeBuilder("body");
+    node.setAttr("x", "1");
+    expect(node.removeAttr("x")).toBe(true);
+    expect(node.removeAttr("x")).toBe(false);
+
+    node.setString("text");
+    expect(() => node.appendChild(new BinaryNodeBuilder("a"))).toThrow(
+      "already has bytes or string content",
+    );
+    expect(() => new BinaryNodeBuilder("")).toThrow("non-empty");
+  });
+
+  it("should work with NoiseSession.encodeFrame", () => {
+    const session = new NoiseSession(new Uint8Array(32), new Uint8Array(4));
+    const fromBuilder = session.encodeFrame(buildReference());
+    const other = new NoiseSession(new Uint8Array(32), new Uint8Array(4));
+    expect(fromBuilder).toEqual(other.encodeFrame(reference));
+  });
+});
+
+describe("InternalBinaryNode re-encoding", () => {
+  it("should reuse the original bytes when untouched", () => {
+    const bytes = encodeNode(reference);
+    expect(encodeNode(decodeNode(bytes))).toEqual(bytes);
+  });
+
+  it("should keep the original bytes after attrs and content are read", () => {
+    // `iq` and `id` spelled out instead of as the dictionary tokens a fresh
+    // encode would use, so only the original bytes round-trip exactly
+    const literal = (s: string) => [252, s.length, ...new TextEncoder().encode(s)];
+    const bytes = new Uint8Array([
+      0,
+      248,
+      4,
+      ...literal("iq"),
+      ...literal("id"),
+      ...literal("1"),
+      ...literal("hi"),
+    ]);
+    const decoded = decodeNode(bytes);
+    expect(decoded.attrs.id).toBe("1");
+    expect(decoded.content).toEqual(new TextEncoder().encode("hi"));
+    expect(encodeNode(decoded)).toEqual(bytes);
+
+    decoded.attrs.id = "2";
+    expect(encodeNode(decoded)).not.toEqual(bytes);
+    expect(decodeNode(encodeNode(decoded)).attrs.id).toBe("2");
+  });
+
+  it("should pick up modifications made from JS", () => {
+    const decoded = decodeNode(encodeNode(reference));
+    decoded.attrs = { id: "2" };
+    expect(decodeNode(encodeNode(decoded)).attrs).toEqual({ id: "2" });
+
+    const other = decodeNode(encodeNode(reference));
+    (other.content as BinaryNode[])[0]!.attrs = { changed: "yes" };
+    expect(decodeNode(encodeNode(other)).getChild("ping")?.attrs.changed).toBe(
+      "yes",
+    );
+  });
+});
+
+describe("Decoded nodes as children", () => {
+  it("should encode edits made through a child lookup", () => {
+    const decoded = decodeNode(encodeNode(reference));
+    decoded.getChild("enc")!.attrs = { v: "3" };
+    const reencoded = decodeNode(encodeNode(decoded));
+    expect(reencoded.getChild("enc")?.attrs).toEqual({ v: "3" });
+    expect(reencoded.getChild("body")?.content).toBe("hi");
+  });
+
+  it("should apply strict mode inside modified decoded children", () => {
+    const child = decodeNode(encodeNode(reference));
+    child.attrs = { bad: {} as never };
+    expect(() =>
+      encodeNode({ tag: "wrap", attrs: {}, content: [child] }, { strict: true }),
+    ).toThrow("wrap.content[0].attrs.bad");
+  });
+
+  it("should splice decoded nodes into builders unchanged", () => {
+    const builder = new BinaryNodeBuilder("wrap");
+    builder.appendNode(decodeNode(encodeNode(reference)));
+    expect(builder.encode()).toEqual(
+      encodeNode({ tag: "wrap", attrs: {}, content: [reference] }),
+    );
+  });
+
+  it("should not treat look-alike objects as native nodes", () => {
+    const fake = {
+      tag: "ping",
+      attrs: {},
+      __marshal: () => encodeNode(reference),
+    } as BinaryNode;
+    expect(decodeNode(encodeNode(fake)).tag).toBe("ping");
+  });
+});
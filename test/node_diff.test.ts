@@ -0,0 +1,89 @@
+import { describe, expect, it } from "bun:test";
+import { encodeBinaryNode } from "baileys";
+import {
+  binaryNodeDiff,
+  binaryNodeEquals,
+  decodeNode,
+  encodeNode,
+  type BinaryNode,
+} from "../dist";
+
+const node: BinaryNode = {
+  tag: "message",
+  attrs: { id: "1", to: "123@s.whatsapp.net", type: "text" },
+  content: [
+    { tag: "body", attrs: {}, content: "hello" },
+    { tag: "enc", attrs: { v: "2" }, content: new Uint8Array([1, 2, 3]) },
+  ],
+};
+
+describe("binaryNodeEquals", () => {
+  it("should ignore attribute order and string/bytes representation", () => {
+    const reordered: BinaryNode = {
+      tag: "message",
+      attrs: { type: "text", to: "123@s.whatsapp.net", id: "1" },
+      content: [
+        { tag: "body", attrs: {}, content: new TextEncoder().encode("hello") },
+        { tag: "enc", attrs: { v: "2" }, content: new Uint8Array([1, 2, 3]) },
+      ],
+    };
+    expect(binaryNodeEquals(node, reordered)).toBe(true);
+  });
+
+  it("should compare encoded bytes from Baileys and decoded nodes", () => {
+    const legacy = encodeBinaryNode(node);
+    expect(binaryNodeEquals(legacy, encodeNode(node))).toBe(true);
+    expect(binaryNodeEquals(decodeNode(legacy), node)).toBe(true);
+  });
+
+  it("should detect differences", () => {
+    expect(binaryNodeEquals(node, { ...node, attrs: { id: "2" } })).toBe(false);
+  });
+});
+
+describe("binaryNodeDiff", () => {
+  it("should list path-addressed differences", () => {
+    const other: BinaryNode = {
+      tag: "message",
+      attrs: { id: "1", to: "456@s.whatsapp.net", extra: "x" },
+      content: [
+        { tag: "body", attrs: {}, content: "bye" },
+        { tag: "enc", attrs: { v: "2" }, content: new Uint8Array([1, 2, 4]) },
+        { tag: "extra", attrs: {} },
+      ],
+    };
+
+    const diff = binaryNodeDiff(node, other);
+    expect(diff.equal).toBe(false);
+    expect(diff.differences).toEqual([
+      {
+        path: "message.attrs.to",
+        kind: "attr",
+        left: "123@s.whatsapp.net",
+        right: "456@s.whatsapp.net",
+      },
+      { path: "message.attrs.type", kind: "attr", left: "text" },
+      { path: "message.attrs.extra", kind: "attr", right: "x" },
+      { path: "message.content", kind: "children", left: "2", right: "3" },
+      {
+        path: "message.content[0].content",
+        kind: "content",
+        left: "hello",
+        right: "bye",
+      },
+      {
+        path: "message.content[1].content",
+        kind: "content",
+        left: "hex:010203",
+        right: "hex:010204",
+      },
+    ]);
+  });
+
+  it("should report equal nodes", () => {
+    expect(binaryNodeDiff(node, encodeNode(node))).toEqual({
+      equal: true,
+      differences: [],
+    });
+  });
+});
@@ -0,0 +1,61 @@
+import { describe, expect, it } from "bun:test";
+import {
+  decodeNode,
+  encodeNode,
+  InternalBinaryNode,
+  type BinaryNode,
+} from "../dist";
+
+const node: BinaryNode = {
+  tag: "message",
+  attrs: { to: "123@s.whatsapp.net", id: "abc", type: "text" },
+  content: [
+    { tag: "body", attrs: {}, content: "hello" },
+    { tag: "enc", attrs: { v: "2" }, content: new Uint8Array([0, 1, 2, 255]) },
+    { tag: "empty", attrs: {} },
+  ],
+};
+
+describe("toPortableJSON / fromPortableJSON", () => {
+  it("should tag byte content as base64 and keep strings", () => {
+    const portable = decodeNode(encodeNode(node)).toPortableJSON();
+    expect(portable).toEqual({
+      tag: "message",
+      attrs: { to: "123@s.whatsapp.net", id: "abc", type: "text" },
+      content: [
+        { tag: "body", attrs: {}, content: "hello" },
+        { tag: "enc", attrs: { v: "2" }, content: { type: "bytes", base64: "AAEC/w==" } },
+        { tag: "empty", attrs: {} },
+      ],
+    });
+  });
+
+  it("should round-trip losslessly through JSON.stringify", () => {
+    const bytes = encodeNode(node);
+    const json = JSON.stringify(decodeNode(bytes).toPortableJSON());
+    expect(encodeNode(InternalBinaryNode.fromPortableJSON(json))).toEqual(bytes);
+    expect(
+      encodeNode(InternalBinaryNode.fromPortableJSON(JSON.parse(json))),
+    ).toEqual(bytes);
+  });
+
+  it("should reflect attrs modified from JS", () => {
+    const decoded = decodeNode(encodeNode(node));
+    decoded.attrs = { id: "changed" };
+    expect(decoded.toPortableJSON().attrs).toEqual({ id: "changed" });
+  });
+
+  it("should reject malformed input with a path", () => {
+    expect(() =>
+      InternalBinaryNode.fromPortableJSON({
+        tag: "iq",
+        attrs: {},
+        content: [{ tag: "x", attrs: {}, content: { type: "bytes", base64: 1 } as any }],
+      }),
+    ).toThrow("iq.content[0].content.base64");
+    expect(() =>
+      InternalBinaryNode.fromPortableJSON({ tag: "iq", attrs: { to: {} as any } }),
+    ).toThrow("iq.attrs.to");
+    expect(() => InternalBinaryNode.fromPortableJSON("{")).toThrow();
+  });
+});
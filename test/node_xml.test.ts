@@ -0,0 +1,106 @@
+import { describe, expect, it } from "bun:test";
+import {
+  binaryNodeToString,
+  decodeNode,
+  encodeNode,
+  parseXmlNode,
+  type BinaryNode,
+} from "../dist";
+
+const node: BinaryNode = {
+  tag: "iq",
+  attrs: { id: "abc", type: "get", xmlns: "w:p" },
+  content: [
+    { tag: "ping", attrs: {} },
+    { tag: "enc", attrs: { v: "2" }, content: new Uint8Array([0x0a, 0xff, 0x10]) },
+  ],
+};
+
+describe("toXML / binaryNodeToString", () => {
+  it("should render indented XML with hex bytes by default", () => {
+    const xml = decodeNode(encodeNode(node)).toXML();
+    expect(xml).toBe(
+      [
+        '<iq id="abc" type="get" xmlns="w:p">',
+        "  <ping/>",
+        '  <enc v="2">hex:0aff10</enc>',
+        "</iq>",
+      ].join("\n"),
+    );
+  });
+
+  it("should support base64, elided bytes and single-line output", () => {
+    const bytes = encodeNode(node);
+    expect(binaryNodeToString(bytes, { indent: 0, bytes: "base64" })).toBe(
+      '<iq id="abc" type="get" xmlns="w:p"><ping/><enc v="2">base64:Cv8Q</enc></iq>',
+    );
+    expect(binaryNodeToString(bytes, { bytes: "length" })).toContain(
+      '<enc v="2">[3 bytes]</enc>',
+    );
+  });
+
+  it("should escape attribute values and text", () => {
+    const xml = decodeNode(
+      encodeNode({ tag: "body", attrs: { q: 'a"<b' }, content: "x & y" }),
+    ).toXML({ bytes: "length" });
+    expect(xml).toContain('q="a&quot;&lt;b"');
+  });
+
+  it("should reflect attrs modified from JS", () => {
+    const decoded = decodeNode(encodeNode(node));
+    decoded.attrs = { id: "changed" };
+    expect(decoded.toXML({ indent: 0 })).toStartWith('<iq id="changed">');
+  });
+});
+
+describe("parseXmlNode", () => {
+  it("should round-trip through encodeNode", () => {
+    const bytes = encodeNode(node);
+    const parsed = parseXmlNode(binaryNodeToString(bytes));
+    expect(encodeNode(parsed)).toEqual(bytes);
+  });
+
+  it("should parse hand-written fixtures", () => {
+    const parsed = parseXmlNode(`
+      <message to='123@s.whatsapp.net' type="text">
+        <body>hello &amp; bye</body>
+        <enc>base64:AQID</enc>
+      </message>
+    `);
+    expect(parsed.tag).toBe("message");
+    expect(parsed.attrs.to).toBe("123@s.whatsapp.net");
+    const [body, enc] = parsed.content as BinaryNode[];
+    expect(body!.content).toBe("hello & bye");
+    expect(enc!.content).toEqual(new Uint8Array([1, 2, 3]));
+  });
+
+  it("should keep string content that looks like bytes a string", () => {
+    for (const content of ["hex:0aff", " base64:AQID", "[3 bytes]", "[]"]) {
+      const bytes = encodeNode({ tag: "body", attrs: {}, content });
+      const parsed = parseXmlNode(binaryNodeToString(bytes));
+      expect(parsed.content).toBe(content);
+      expect(encodeNode(parsed)).toEqual(bytes);
+    }
+  });
+
+  it("should tell empty string, empty list and no content apart", () => {
+    const xmls = ["", [], undefined].map((content) => {
+      const bytes = encodeNode({ tag: "a", attrs: {}, content });
+      const xml = binaryNodeToString(bytes);
+      expect(encodeNode(parseXmlNode(xml))).toEqual(bytes);
+      return xml;
+    });
+    expect(new Set(xmls).size).toBe(3);
+  });
+
+  it("should bound nesting depth", () => {
+    const deep = "<a>".repeat(10_000) + "</a>".repeat(10_000);
+    expect(() => parseXmlNode(deep)).toThrow("levels deep");
+  });
+
+  it("should reject malformed input", () => {
+    expect(() => parseXmlNode("<a><b></a>")).toThrow("Invalid XML node");
+    expect(() => parseXmlNode("<a>[3 bytes]</a>")).toThrow("elided");
+    expect(() => parseXmlNode("<a/><b/>")).toThrow("trailing");
+  });
+});
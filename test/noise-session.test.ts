@@ -1,8 +1,11 @@
 import { describe, it, expect } from "bun:test";
 import {
   NoiseSession,
+  calculateSignature,
   encodeNode,
+  generateKeyPair,
   getWAConnHeader,
+  verifyCertificateChain,
   type BinaryNode,
 } from "../dist";
 import { randomBytes } from "crypto";
@@ -14,6 +17,7 @@ import {
   Curve,
 } from "baileys/lib/Utils/crypto";
 import { makeNoiseHandler } from "baileys/lib/Utils/noise-handler";
+import { proto } from "baileys";
 
 // Pino logger mock for makeNoiseHandler
 const mockLogger = {
@@ -56,6 +60,34 @@ describe("NoiseSession", () => {
   // Use the real WA_CONN_HEADER from wacore-binary
   const testNoiseHeader = Buffer.from(getWAConnHeader());
 
+  function connectedPair() {
+    const ephemeral = generateKeyPair();
+    const serverStatic = generateKeyPair();
+    const client = new NoiseSession(ephemeral.pubKey.subarray(1), testNoiseHeader, undefined);
+    const server = NoiseSession.responder(testNoiseHeader);
+
+    const [helloBytes] = server.decodeFrame(client.startHandshake(ephemeral));
+    const { clientHello } = proto.HandshakeMessage.decode(helloBytes);
+    const serverHello = server.respondHello(
+      clientHello!.ephemeral!,
+      serverStatic.privKey,
+      serverStatic.pubKey.subarray(1),
+      randomBytes(16),
+    );
+    const [serverHelloBytes] = client.decodeFrame(
+      server.encodeFrameRaw(proto.HandshakeMessage.encode({ serverHello }).finish()),
+    );
+    const [finishBytes] = server.decodeFrame(
+      client.completeHandshake(serverHelloBytes, generateKeyPair(), randomBytes(8), {
+        skipVerification: true,
+      }),
+    );
+    const { clientFinish } = proto.HandshakeMessage.decode(finishBytes);
+    server.processClientFinish(clientFinish!.static!, clientFinish!.payload!);
+    server.finishInit();
+    return { client, server };
+  }
+
   describe("constructor", () => {
     it("should create a new session", () => {
       const publicKey = randomBytes(32);
@@ -998,4 +1030,621 @@ describe("NoiseSession", () => {
       expect(hex(wasmEncrypted)).toBe(hex(jsEncrypted));
     });
   });
+
+  describe("exportState/importState", () => {
+    function finishedSession() {
+      const session = new NoiseSession(randomBytes(32), testNoiseHeader, undefined);
+      session.authenticate(randomBytes(32));
+      session.mixIntoKey(randomBytes(32));
+      session.encrypt(Buffer.from("handshake payload"));
+      session.finishInit();
+      return session;
+    }
+
+    it("should resume with the same keys and counters", () => {
+      const session = finishedSession();
+      session.encrypt(Buffer.from("first"));
+      session.encrypt(Buffer.from("second"));
+
+      const restored = NoiseSession.importState(session.exportState());
+      expect(restored.isFinished).toBe(true);
+
+      const plaintext = Buffer.from("third");
+      expect(hex(restored.encrypt(plaintext))).toBe(hex(session.encrypt(plaintext)));
+      expect(hex(restored.encrypt(plaintext))).toBe(hex(session.encrypt(plaintext)));
+    });
+
+    it("should decrypt the peer's frames after importState", () => {
+      const { client, server } = connectedPair();
+      expect(client.decodeFrame(server.encodeFrame({ tag: "before", attrs: {} }))).toHaveLength(
+        1,
+      );
+
+      const restored = NoiseSession.importState(client.exportState());
+      const [node] = restored.decodeFrame(
+        server.encodeFrame({ tag: "after", attrs: { id: "1" } }),
+      );
+      expect(node.tag).toBe("after");
+      expect(node.attrs).toEqual({ id: "1" });
+
+      const [echoed] = server.decodeFrame(restored.encodeFrame({ tag: "reply", attrs: {} }));
+      expect(echoed.tag).toBe("reply");
+    });
+
+    it("should only hold the transport keys and counters", () => {
+      // "WANS", version, role, two counters, two 32-byte keys
+      expect(finishedSession().exportState()).toHaveLength(4 + 1 + 1 + 8 + 64);
+    });
+
+    it("should refuse to export mid-handshake or with a partial frame", () => {
+      const pending = new NoiseSession(randomBytes(32), testNoiseHeader, undefined);
+      expect(() => pending.exportState()).toThrow("handshake is not finished");
+
+      const session = finishedSession();
+      session.decodeFrame(new Uint8Array([0, 0, 10, 1, 2]));
+      expect(() => session.exportState()).toThrow("partial frame");
+    });
+
+    it("should reject corrupted state", () => {
+      const state = finishedSession().exportState();
+      expect(() => NoiseSession.importState(state.subarray(0, state.length - 1))).toThrow(
+        "importState failed",
+      );
+      expect(() => NoiseSession.importState(new Uint8Array([1, 2, 3, 4, 5]))).toThrow(
+        "not a NoiseSession state",
+      );
+    });
+  });
+
+  describe("responder", () => {
+    const raw = (pair: { pubKey: Uint8Array; privKey: Uint8Array }) => ({
+      pub: pair.pubKey.subarray(1),
+      priv: pair.privKey,
+    });
+
+    it("should complete a handshake against an in-process initiator", () => {
+      const clientEphemeral = raw(generateKeyPair());
+      const clientNoise = raw(generateKeyPair());
+      const serverStatic = raw(generateKeyPair());
+      const certificate = randomBytes(64);
+      const clientPayload = randomBytes(48);
+
+      const client = new NoiseSession(clientEphemeral.pub, testNoiseHeader, undefined);
+      const server = NoiseSession.responder(testNoiseHeader);
+
+      // ClientHello: the intro header is stripped by the responder
+      const [hello] = server.decodeFrame(client.encodeFrameRaw(clientEphemeral.pub));
+      expect(hex(hello)).toBe(hex(clientEphemeral.pub));
+
+      const serverHello = server.respondHello(
+        hello,
+        serverStatic.priv,
+        serverStatic.pub,
+        certificate,
+      );
+      const decryptedCert = client.processHandshakeInit(
+        serverHello.ephemeral,
+        serverHello.static,
+        serverHello.payload,
+        clientEphemeral.priv,
+      );
+      expect(hex(decryptedCert)).toBe(hex(certificate));
+      expect(hex(client.getHash())).toBe(hex(server.getHash()));
+
+      const keyEnc = client.processHandshakeFinish(
+        clientNoise.pub,
+        clientNoise.priv,
+        serverHello.ephemeral,
+      );
+      const payloadEnc = client.encrypt(clientPayload);
+      client.finishInit();
+
+      const finish = server.processClientFinish(keyEnc, payloadEnc);
+      expect(hex(finish.static)).toBe(hex(clientNoise.pub));
+      expect(hex(finish.payload)).toBe(hex(clientPayload));
+      server.finishInit();
+
+      const ping: BinaryNode = { tag: "iq", attrs: { id: "1", type: "get", xmlns: "w:p" } };
+      const [atServer] = server.decodeFrame(client.encodeFrame(ping));
+      expect(atServer.attrs.xmlns).toBe("w:p");
+      const [atClient] = client.decodeFrame(
+        server.encodeFrame({ tag: "iq", attrs: { id: "1", type: "result" } }),
+      );
+      expect(atClient.attrs.type).toBe("result");
+    });
+
+    it("should drop an intro header with routing info, even split into chunks", () => {
+      const ephemeral = generateKeyPair();
+      const routingInfo = Buffer.from([0x08, 0x02, 0x08, 0x12, 0x08, 0x0d]);
+      const client = new NoiseSession(ephemeral.pubKey.subarray(1), testNoiseHeader, routingInfo);
+      const server = NoiseSession.responder(testNoiseHeader);
+
+      const hello = client.startHandshake(ephemeral);
+      const frames = [...hello].flatMap((byte) => server.decodeFrame(new Uint8Array([byte])));
+      expect(frames).toHaveLength(1);
+      const { clientHello } = proto.HandshakeMessage.decode(frames[0]);
+      expect(hex(clientHello!.ephemeral!)).toBe(hex(ephemeral.pubKey.subarray(1)));
+    });
+
+    it("should reject an unexpected intro header", () => {
+      const server = NoiseSession.responder(testNoiseHeader);
+      const badVersion = new Uint8Array([0x45, 0x44, 9, 9, 0, 0, 0, ...testNoiseHeader]);
+      expect(() => server.decodeFrame(badVersion)).toThrow("Unexpected intro header");
+      const other = NoiseSession.responder(testNoiseHeader);
+      expect(() => other.decodeFrame(new Uint8Array(testNoiseHeader.length))).toThrow(
+        "Unexpected intro header",
+      );
+    });
+
+    it("should reject a tampered client finish", () => {
+      const clientEphemeral = raw(generateKeyPair());
+      const serverStatic = raw(generateKeyPair());
+      const server = NoiseSession.responder(testNoiseHeader);
+      server.respondHello(clientEphemeral.pub, serverStatic.priv, serverStatic.pub, randomBytes(8));
+      expect(() => server.processClientFinish(randomBytes(48), randomBytes(32))).toThrow(
+        "decrypt static failed",
+      );
+    });
+
+    it("should refuse responder steps on an initiator", () => {
+      const session = new NoiseSession(randomBytes(32), testNoiseHeader, undefined);
+      expect(() =>
+        session.respondHello(randomBytes(32), randomBytes(32), randomBytes(32), randomBytes(8)),
+      ).toThrow("Only a responder session");
+    });
+  });
+
+  describe("startHandshake/completeHandshake", () => {
+    it("should drive the client handshake against a responder", () => {
+      const ephemeral = generateKeyPair();
+      const noiseKey = generateKeyPair();
+      const serverStatic = generateKeyPair();
+      const certificate = randomBytes(64);
+      const clientPayload = randomBytes(40);
+
+      const client = new NoiseSession(ephemeral.pubKey.subarray(1), testNoiseHeader, undefined);
+      const server = NoiseSession.responder(testNoiseHeader);
+
+      const [helloBytes] = server.decodeFrame(client.startHandshake(ephemeral));
+      const { clientHello } = proto.HandshakeMessage.decode(helloBytes);
+      expect(hex(clientHello!.ephemeral!)).toBe(hex(ephemeral.pubKey.subarray(1)));
+
+      const serverHello = server.respondHello(
+        clientHello!.ephemeral!,
+        serverStatic.privKey,
+        serverStatic.pubKey.subarray(1),
+        certificate,
+      );
+      const [serverHelloBytes] = client.decodeFrame(
+        server.encodeFrameRaw(proto.HandshakeMessage.encode({ serverHello }).finish()),
+      );
+
+      const finishFrame = client.completeHandshake(serverHelloBytes, noiseKey, clientPayload, {
+        skipVerification: true,
+      });
+      expect(client.isFinished).toBe(true);
+      expect(hex(client.serverCertificate!)).toBe(hex(certificate));
+
+      const [finishBytes] = server.decodeFrame(finishFrame);
+      const { clientFinish } = proto.HandshakeMessage.decode(finishBytes);
+      const finish = server.processClientFinish(clientFinish!.static!, clientFinish!.payload!);
+      expect(hex(finish.static)).toBe(hex(noiseKey.pubKey.subarray(1)));
+      expect(hex(finish.payload)).toBe(hex(clientPayload));
+      server.finishInit();
+
+      const [node] = server.decodeFrame(
+        client.encodeFrame({ tag: "iq", attrs: { id: "2", type: "get" } }),
+      );
+      expect(node.attrs.id).toBe("2");
+    });
+
+    it("should require startHandshake and a ServerHello", () => {
+      const ephemeral = generateKeyPair();
+      const client = new NoiseSession(ephemeral.pubKey.subarray(1), testNoiseHeader, undefined);
+      expect(() =>
+        client.completeHandshake(new Uint8Array(), generateKeyPair(), new Uint8Array()),
+      ).toThrow("startHandshake must be called first");
+
+      client.startHandshake(ephemeral);
+      expect(() =>
+        client.completeHandshake(
+          proto.HandshakeMessage.encode({ clientHello: {} }).finish(),
+          generateKeyPair(),
+          new Uint8Array(),
+        ),
+      ).toThrow("missing ServerHello");
+    });
+
+    it("should reject an ephemeral pair other than the constructor key", () => {
+      const ephemeral = generateKeyPair();
+      const client = new NoiseSession(ephemeral.pubKey.subarray(1), testNoiseHeader, undefined);
+      expect(() => client.startHandshake(generateKeyPair())).toThrow("does not match");
+      expect(client.startHandshake(ephemeral).length).toBeGreaterThan(0);
+    });
+
+    it("should keep the ephemeral key after a malformed ServerHello", () => {
+      const ephemeral = generateKeyPair();
+      const serverStatic = generateKeyPair();
+      const client = new NoiseSession(ephemeral.pubKey.subarray(1), testNoiseHeader, undefined);
+      const server = NoiseSession.responder(testNoiseHeader);
+
+      const [helloBytes] = server.decodeFrame(client.startHandshake(ephemeral));
+      const { clientHello } = proto.HandshakeMessage.decode(helloBytes);
+      const serverHello = server.respondHello(
+        clientHello!.ephemeral!,
+        serverStatic.privKey,
+        serverStatic.pubKey.subarray(1),
+        randomBytes(16),
+      );
+      const [serverHelloBytes] = client.decodeFrame(
+        server.encodeFrameRaw(proto.HandshakeMessage.encode({ serverHello }).finish()),
+      );
+
+      expect(() =>
+        client.completeHandshake(new Uint8Array([0xff]), generateKeyPair(), new Uint8Array()),
+      ).toThrow("completeHandshake failed");
+      client.completeHandshake(serverHelloBytes, generateKeyPair(), randomBytes(8), {
+        skipVerification: true,
+      });
+      expect(client.isFinished).toBe(true);
+    });
+  });
+
+  describe("verifyServerCertificate", () => {
+    const root = generateKeyPair();
+    const intermediateKey = generateKeyPair();
+    const serverStatic = generateKeyPair();
+    const now = 1_700_000_000;
+
+    function makeChain({
+      leafKey = serverStatic.pubKey.subarray(1),
+      leafIssuer = 7,
+      notAfter = now + 3600,
+      signer = root.privKey,
+    } = {}) {
+      const { NoiseCertificate } = proto.CertChain;
+      const intermediateDetails = NoiseCertificate.Details.encode({
+        serial: 7,
+        issuerSerial: 0,
+        key: intermediateKey.pubKey.subarray(1),
+        notBefore: now - 3600,
+        notAfter,
+      }).finish();
+      const leafDetails = NoiseCertificate.Details.encode({
+        serial: 42,
+        issuerSerial: leafIssuer,
+        key: leafKey,
+        notBefore: now - 3600,
+        notAfter,
+      }).finish();
+      return proto.CertChain.encode({
+        intermediate: {
+          details: intermediateDetails,
+          signature: calculateSignature(signer, intermediateDetails),
+        },
+        leaf: {
+          details: leafDetails,
+          signature: calculateSignature(intermediateKey.privKey, leafDetails),
+        },
+      }).finish();
+    }
+
+    const options = { rootPublicKey: root.pubKey, now };
+    const staticKey = serverStatic.pubKey.subarray(1);
+
+    it("should accept a chain signed by the root and bound to the static key", () => {
+      const result = verifyCertificateChain(makeChain(), staticKey, options);
+      expect(result.valid).toBe(true);
+      expect(result.error).toBeUndefined();
+      expect(result.intermediate!.serial).toBe(7);
+      expect(result.leaf!.serial).toBe(42);
+      expect(result.leaf!.issuerSerial).toBe(7);
+      expect(hex(result.leaf!.key)).toBe(hex(staticKey));
+    });
+
+    it("should report why a chain is rejected", () => {
+      const cases: [Uint8Array, string, object?][] = [
+        [randomBytes(16), "malformed"],
+        [makeChain({ signer: generateKeyPair().privKey }), "intermediateSignature"],
+        [makeChain(), "intermediateSignature", { now }],
+        [makeChain(), "intermediateIssuer", { ...options, rootSerial: 1 }],
+        [makeChain({ leafIssuer: 8 }), "leafIssuer"],
+        [makeChain({ notAfter: now - 1 }), "expired"],
+        [makeChain(), "notYetValid", { ...options, now: now - 7200 }],
+        [makeChain({ leafKey: randomBytes(32) }), "staticKeyMismatch"],
+      ];
+      for (const [chain, error, opts] of cases) {
+        const result = verifyCertificateChain(chain, staticKey, (opts ?? options) as any);
+        expect(result.valid).toBe(false);
+        expect(result.error).toBe(error as any);
+      }
+    });
+
+    it("should verify the certificate received during completeHandshake", () => {
+      const handshake = (certificate: Uint8Array) => {
+        const ephemeral = generateKeyPair();
+        const client = new NoiseSession(ephemeral.pubKey.subarray(1), testNoiseHeader, undefined);
+        const server = NoiseSession.responder(testNoiseHeader);
+        const [helloBytes] = server.decodeFrame(client.startHandshake(ephemeral));
+        const { clientHello } = proto.HandshakeMessage.decode(helloBytes);
+        const serverHello = server.respondHello(
+          clientHello!.ephemeral!,
+          serverStatic.privKey,
+          staticKey,
+          certificate,
+        );
+        const [serverHelloBytes] = client.decodeFrame(
+          server.encodeFrameRaw(proto.HandshakeMessage.encode({ serverHello }).finish()),
+        );
+        return { client, serverHelloBytes };
+      };
+
+      const ok = handshake(makeChain());
+      expect(() => ok.client.verifyServerCertificate()).toThrow("no ServerHello");
+      ok.client.completeHandshake(ok.serverHelloBytes, generateKeyPair(), randomBytes(8), options);
+      expect(ok.client.isFinished).toBe(true);
+      expect(ok.client.verifyServerCertificate(options).valid).toBe(true);
+      // WhatsApp's production root did not sign this chain
+      expect(ok.client.verifyServerCertificate({ now }).error).toBe("intermediateSignature");
+
+      const bad = handshake(makeChain({ leafKey: randomBytes(32) }));
+      expect(() =>
+        bad.client.completeHandshake(
+          bad.serverHelloBytes,
+          generateKeyPair(),
+          randomBytes(8),
+          options,
+        ),
+      ).toThrow("invalid server certificate (staticKeyMismatch)");
+      expect(bad.client.isFinished).toBe(false);
+
+      // Without options the chain must come from WhatsApp's production root.
+      const unknownRoot = handshake(makeChain());
+      expect(() =>
+        unknownRoot.client.completeHandshake(
+          unknownRoot.serverHelloBytes,
+          generateKeyPair(),
+          randomBytes(8),
+        ),
+      ).toThrow("invalid server certificate (intermediateSignature)");
+      expect(unknownRoot.client.isFinished).toBe(false);
+    });
+  });
+
+  describe("frameCounters", () => {
+    function sessionAt(read: number, write: number) {
+      const session = new NoiseSession(randomBytes(32), testNoiseHeader, undefined);
+      session.authenticate(randomBytes(32));
+      session.mixIntoKey(randomBytes(32));
+      session.finishInit();
+      // state layout: "WANS", version, role, read counter, write counter
+      const state = session.exportState();
+      const view = new DataView(state.buffer, state.byteOffset);
+      view.setUint32(6, read);
+      view.setUint32(10, write);
+      return NoiseSession.importState(state);
+    }
+
+    it("should count transport frames in each direction", () => {
+      const session = sessionAt(0, 0);
+      session.encrypt(Buffer.from("a"));
+      session.encrypt(Buffer.from("b"));
+      expect(session.frameCounters).toEqual({ read: 0, write: 2, remaining: 0xffffffff - 2 });
+    });
+
+    it("should close a direction before its nonce wraps", () => {
+      const session = sessionAt(0, 0xfffffffe);
+      session.encrypt(Buffer.from("last"));
+      expect(session.frameCounters.remaining).toBe(0);
+      expect(() => session.encrypt(Buffer.from("wrapped"))).toThrow(
+        "Noise write counter exhausted",
+      );
+      expect(() => session.encodeFrameRaw(Buffer.from("wrapped"))).toThrow(
+        "Noise write counter exhausted",
+      );
+      expect(session.frameCounters.write).toBe(0xffffffff);
+
+      const reader = sessionAt(0xffffffff, 0);
+      expect(() => reader.decrypt(randomBytes(32))).toThrow("Noise read counter exhausted");
+    });
+  });
+
+  describe("pipeThrough/frameEncoder", () => {
+    function streamOf<T>(chunks: T[]): ReadableStream<T> {
+      return new ReadableStream({
+        start(controller) {
+          for (const chunk of chunks) controller.enqueue(chunk);
+          controller.close();
+        },
+      });
+    }
+
+    async function collect<T>(stream: ReadableStream<T>): Promise<T[]> {
+      const items: T[] = [];
+      for await (const item of stream as any) items.push(item);
+      return items;
+    }
+
+    it("should decode nodes encoded by a frameEncoder, across chunk boundaries", async () => {
+      const { client, server } = connectedPair();
+      const nodes: BinaryNode[] = [1, 2, 3].map((i) => ({
+        tag: "iq",
+        attrs: { id: String(i), type: "result" },
+      }));
+
+      const frames = await collect(streamOf(nodes).pipeThrough(server.frameEncoder()));
+      expect(frames.length).toBe(3);
+      const bytes = Buffer.concat(frames);
+      const chunks = [];
+      for (let i = 0; i < bytes.length; i += 5) chunks.push(bytes.subarray(i, i + 5));
+
+      const decoded = await collect(client.pipeThrough(streamOf(chunks)));
+      expect(decoded.map((node) => node.attrs.id)).toEqual(["1", "2", "3"]);
+      expect(client.frameCounters.read).toBe(3);
+      expect(server.frameCounters.write).toBe(3);
+    });
+
+    it("should share counters with direct calls on the session", async () => {
+      const { client, server } = connectedPair();
+      const first = server.encodeFrame({ tag: "a", attrs: {} });
+      const [second] = await collect(
+        streamOf<BinaryNode>([{ tag: "b", attrs: {} }]).pipeThrough(server.frameEncoder()),
+      );
+      expect(client.decodeFrame(first)[0].tag).toBe("a");
+      const [node] = await collect(client.pipeThrough(streamOf([second])));
+      expect(node.tag).toBe("b");
+    });
+
+    it("should error the stream on a frame that fails to decrypt", async () => {
+      const { client, server } = connectedPair();
+      const good = server.encodeFrame({ tag: "ok", attrs: {} });
+      const bad = new Uint8Array([0, 0, 20, ...randomBytes(20)]);
+      const reader = client.pipeThrough(streamOf([good, bad])).getReader();
+
+      expect((await reader.read()).value?.tag).toBe("ok");
+      await expect(reader.read()).rejects.toThrow("Decryption failed");
+    });
+
+    it("should enqueue the nodes decoded before a failing frame in the same chunk", async () => {
+      const { client, server } = connectedPair();
+      const good = server.encodeFrame({ tag: "ok", attrs: {} });
+      const bad = new Uint8Array([0, 0, 20, ...randomBytes(20)]);
+      const reader = client.pipeThrough(streamOf([new Uint8Array([...good, ...bad])])).getReader();
+
+      expect((await reader.read()).value?.tag).toBe("ok");
+      await expect(reader.read()).rejects.toThrow("Decryption failed");
+    });
+
+    it("should error the stream when the input ends inside a frame", async () => {
+      const { client, server } = connectedPair();
+      const frame = server.encodeFrame({ tag: "cut", attrs: {} });
+      const reader = client.pipeThrough(streamOf([frame.subarray(0, 5)])).getReader();
+      await expect(reader.read()).rejects.toThrow("truncated frame");
+    });
+
+    it("should require a finished handshake", () => {
+      const session = new NoiseSession(randomBytes(32), testNoiseHeader, undefined);
+      expect(() => session.pipeThrough(streamOf<Uint8Array>([]))).toThrow(
+        "finished handshake",
+      );
+    });
+  });
+
+  describe("decodeFrameSettled", () => {
+    it("should keep the good frames of a batch and report the bad ones", () => {
+      const { client, server } = connectedPair();
+      const first = server.encodeFrame({ tag: "iq", attrs: { id: "1" } });
+      const corrupt = server.encodeFrame({ tag: "iq", attrs: { id: "2" } });
+      corrupt[corrupt.length - 1] ^= 0xff;
+      const third = server.encodeFrame({ tag: "iq", attrs: { id: "3" } });
+
+      const { nodes, errors } = client.decodeFrameSettled(
+        Buffer.concat([first, corrupt, third]),
+      );
+      expect(nodes.map((node: any) => node?.attrs.id)).toEqual(["1", undefined, "3"]);
+      expect(errors.length).toBe(1);
+      expect(errors[0]!.index).toBe(1);
+      expect(nodes[errors[0]!.index]).toBeUndefined();
+      expect(errors[0]!.stage).toBe("decrypt");
+      expect(errors[0]!.message).toContain("Decryption failed");
+      // the failed frame still consumed its counter
+      expect(client.frameCounters.read).toBe(3);
+    });
+
+    it("should report frames that decrypt but do not unmarshal", () => {
+      const { client, server } = connectedPair();
+      const garbage = server.encodeFrameRaw(new Uint8Array([0x02, 0x00, 0x01]));
+      const good = server.encodeFrame({ tag: "ok", attrs: {} });
+
+      const { nodes, errors } = client.decodeFrameSettled(Buffer.concat([garbage, good]));
+      expect(nodes.length).toBe(2);
+      expect((nodes[1] as any).tag).toBe("ok");
+      expect(errors.map((e) => [e.index, e.stage])).toEqual([[0, "unmarshal"]]);
+    });
+
+    it("should return raw frames before the handshake finishes", () => {
+      const session = new NoiseSession(randomBytes(32), testNoiseHeader, undefined);
+      const { nodes, errors } = session.decodeFrameSettled(new Uint8Array([0, 0, 2, 7, 8]));
+      expect(hex(nodes[0] as Uint8Array)).toBe("0708");
+      expect(errors).toEqual([]);
+    });
+  });
+
+  describe("frame limits", () => {
+    function expectLimitError(fn: () => unknown, limit: string) {
+      try {
+        fn();
+        throw new Error("expected a FrameLimitError");
+      } catch (err: any) {
+        expect(err.name).toBe("FrameLimitError");
+        expect(err.limit).toBe(limit);
+      }
+    }
+
+    it("should reject an oversized length prefix before its body arrives", () => {
+      const session = new NoiseSession(randomBytes(32), testNoiseHeader, undefined, {
+        maxFrameSize: 1024,
+      });
+      expect(session.decodeFrame(new Uint8Array([0, 4, 0, ...randomBytes(1024)])).length).toBe(
+        1,
+      );
+      // the header alone, split across calls, is enough to fail
+      session.decodeFrame(new Uint8Array([0, 4]));
+      expectLimitError(() => session.decodeFrame(new Uint8Array([1])), "maxFrameSize");
+      expect(session.bufferedBytes).toBe(2);
+
+      session.clearBuffer();
+      expect(session.decodeFrame(new Uint8Array([0, 0, 1, 9])).length).toBe(1);
+    });
+
+    it("should cap the bytes buffered for an incomplete frame", () => {
+      const session = new NoiseSession(randomBytes(32), testNoiseHeader, undefined, {
+        maxBufferedBytes: 64,
+      });
+      // complete frames in one chunk are never buffered
+      const frame = new Uint8Array([0, 0, 100, ...randomBytes(100)]);
+      expect(session.decodeFrame(Buffer.concat([frame, frame])).length).toBe(2);
+
+      session.decodeFrame(frame.subarray(0, 60));
+      expectLimitError(() => session.decodeFrame(frame.subarray(60, 70)), "maxBufferedBytes");
+      expect(session.bufferedBytes).toBe(60);
+    });
+
+    it("should apply limits to responders, imported sessions and streams", async () => {
+      const responder = NoiseSession.responder(testNoiseHeader, { maxFrameSize: 8 });
+      responder.decodeFrame(testNoiseHeader);
+      expectLimitError(() => responder.decodeFrame(new Uint8Array([0, 0, 9])), "maxFrameSize");
+
+      const { client } = connectedPair();
+      const imported = NoiseSession.importState(client.exportState(), { maxFrameSize: 8 });
+      expectLimitError(() => imported.decodeFrame(new Uint8Array([0, 0, 9])), "maxFrameSize");
+
+      const reader = imported
+        .pipeThrough(
+          new ReadableStream({
+            start(controller) {
+              controller.enqueue(new Uint8Array([1, 0, 0]));
+              controller.close();
+            },
+          }),
+        )
+        .getReader();
+      await expect(reader.read()).rejects.toThrow("exceeds maxFrameSize");
+    });
+
+    it("should refuse to encode payloads the 24-bit length cannot hold", () => {
+      const session = new NoiseSession(randomBytes(32), testNoiseHeader, undefined);
+      expect(() => session.encodeFrameRaw(new Uint8Array(0x1000000))).toThrow(
+        "exceeds the 24-bit frame length limit",
+      );
+      expect(session.encodeFrameRaw(new Uint8Array(16)).length).toBeGreaterThan(16);
+
+      const { client } = connectedPair();
+      expect(() => client.encodeFrameRaw(new Uint8Array(0xffffff - 15))).toThrow(
+        "24-bit frame length limit",
+      );
+      // the refused frame did not consume a nonce
+      expect(client.frameCounters.write).toBe(0);
+    });
+  });
 });
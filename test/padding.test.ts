@@ -0,0 +1,42 @@
+import { describe, expect, it } from "bun:test";
+import { unpadRandomMax16, writeRandomPadMax16 } from "baileys/lib/Utils/generics";
+import { padMessage, unpadMessage } from "../dist";
+
+const message = new Uint8Array([0x0a, 0x02, 0x68, 0x69]);
+
+describe("padMessage / unpadMessage", () => {
+  it("should append 1-15 bytes that each hold the pad length", () => {
+    for (let i = 0; i < 64; i++) {
+      const padded = padMessage(message);
+      const pad = padded[padded.length - 1]!;
+      expect(pad).toBeGreaterThanOrEqual(1);
+      expect(pad).toBeLessThanOrEqual(15);
+      expect(padded.length).toBe(message.length + pad);
+      expect(padded.subarray(message.length).every((b) => b === pad)).toBe(true);
+      expect(unpadMessage(padded)).toEqual(message);
+    }
+  });
+
+  it("should interoperate with Baileys", () => {
+    expect(unpadMessage(writeRandomPadMax16(message))).toEqual(message);
+    expect(new Uint8Array(unpadRandomMax16(padMessage(message)))).toEqual(message);
+  });
+
+  it("should throw a PaddingError on malformed padding", () => {
+    const cases: [Uint8Array, string][] = [
+      [new Uint8Array([]), "empty"],
+      [new Uint8Array([1, 2, 0]), "length"],
+      [new Uint8Array([1, 17]), "length"],
+      [new Uint8Array([1, 2, 3, 3]), "bytes"],
+    ];
+    for (const [input, reason] of cases) {
+      try {
+        unpadMessage(input);
+        throw new Error("expected unpadMessage to throw");
+      } catch (err: any) {
+        expect(err.name).toBe("PaddingError");
+        expect(err.reason).toBe(reason);
+      }
+    }
+  });
+});
@@ -0,0 +1,152 @@
+import { describe, it, expect } from "bun:test";
+import { proto } from "baileys";
+import {
+  decodeHistorySync,
+  decodeMessage,
+  decodeSyncActionValue,
+  decodeWebMessageInfo,
+  encodeHistorySync,
+  encodeMessage,
+  encodeSyncActionValue,
+  encodeWebMessageInfo,
+} from "../dist";
+
+function hex(buffer: Uint8Array): string {
+  return Buffer.from(buffer).toString("hex");
+}
+
+describe("Message proto", () => {
+  const message = {
+    extendedTextMessage: {
+      text: "hello from wasm",
+      matchedText: "https://example.com",
+    },
+  };
+
+  it("should encode identically to protobufjs", () => {
+    const expected = proto.Message.encode(
+      proto.Message.fromObject(message),
+    ).finish();
+    expect(hex(encodeMessage(message))).toBe(hex(expected));
+  });
+
+  it("should decode protobufjs output", () => {
+    const bytes = proto.Message.encode(proto.Message.fromObject(message)).finish();
+    const decoded = decodeMessage(bytes) as typeof message;
+    expect(decoded.extendedTextMessage.text).toBe("hello from wasm");
+    expect(decoded.extendedTextMessage.matchedText).toBe("https://example.com");
+  });
+
+  it("should round-trip bytes fields as Uint8Array", () => {
+    const image = {
+      imageMessage: {
+        url: "https://mmg.whatsapp.net/image",
+        mimetype: "image/jpeg",
+        mediaKey: new Uint8Array(32).fill(7),
+        fileSha256: new Uint8Array(32).fill(9),
+        fileLength: 1234,
+      },
+    };
+    const bytes = encodeMessage(image);
+    const expected = proto.Message.encode(
+      proto.Message.fromObject(image),
+    ).finish();
+    expect(hex(bytes)).toBe(hex(expected));
+
+    const decoded = decodeMessage(bytes);
+    const mediaKey = decoded.imageMessage?.mediaKey;
+    expect(mediaKey).toBeInstanceOf(Uint8Array);
+    expect(hex(mediaKey!)).toBe(hex(image.imageMessage.mediaKey));
+    expect(hex(decoded.imageMessage!.fileSha256!)).toBe(
+      hex(image.imageMessage.fileSha256),
+    );
+    expect(decoded.imageMessage?.fileLength).toBe(1234n);
+    expect(hex(encodeMessage(decoded))).toBe(hex(expected));
+  });
+
+  it("should keep uint64 fields above 2^53 exact", () => {
+    const fileLength = 2n ** 53n + 1n;
+    const bytes = encodeMessage({ imageMessage: { fileLength } });
+    const viaProtobufjs = proto.Message.toObject(proto.Message.decode(bytes), {
+      longs: String,
+    });
+    expect(viaProtobufjs.imageMessage?.fileLength).toBe(fileLength.toString());
+
+    const decoded = decodeMessage(bytes);
+    expect(decoded.imageMessage?.fileLength).toBe(fileLength);
+    expect(hex(encodeMessage(decoded))).toBe(hex(bytes));
+  });
+
+  it("should reject malformed bytes", () => {
+    expect(() => decodeMessage(new Uint8Array([0x0a, 0xff]))).toThrow(
+      "Failed to decode Message",
+    );
+  });
+});
+
+describe("WebMessageInfo proto", () => {
+  it("should round-trip the message key", () => {
+    const info = {
+      key: { remoteJid: "123@s.whatsapp.net", fromMe: true, id: "ABCDEF" },
+      message: { conversation: "hi" },
+    };
+    const decoded = decodeWebMessageInfo(encodeWebMessageInfo(info));
+    expect(decoded.key).toMatchObject(info.key);
+    expect((decoded.message as { conversation: string }).conversation).toBe("hi");
+  });
+});
+
+describe("SyncActionValue proto", () => {
+  it("should match protobufjs for a mute action", () => {
+    const value = { timestamp: 1_700_000_000, muteAction: { muted: true } };
+    const expected = proto.SyncActionValue.encode(
+      proto.SyncActionValue.fromObject(value),
+    ).finish();
+    expect(hex(encodeSyncActionValue(value))).toBe(hex(expected));
+    expect(decodeSyncActionValue(expected).timestamp).toBe(1_700_000_000n);
+  });
+});
+
+describe("HistorySync proto", () => {
+  const history = {
+    syncType: proto.HistorySync.HistorySyncType.RECENT,
+    chunkOrder: 1,
+    conversations: [
+      {
+        id: "123@s.whatsapp.net",
+        messages: [
+          {
+            msgOrderId: 1,
+            message: {
+              key: { remoteJid: "123@s.whatsapp.net", fromMe: false, id: "MSG1" },
+              message: { conversation: "from history" },
+              messageTimestamp: 1_700_000_000,
+            },
+          },
+        ],
+      },
+    ],
+  };
+
+  it("should encode identically to protobufjs", () => {
+    const expected = proto.HistorySync.encode(
+      proto.HistorySync.fromObject(history),
+    ).finish();
+    expect(hex(encodeHistorySync(history))).toBe(hex(expected));
+  });
+
+  it("should decode protobufjs output", () => {
+    const bytes = proto.HistorySync.encode(
+      proto.HistorySync.fromObject(history),
+    ).finish();
+    const decoded = decodeHistorySync(bytes);
+    expect(decoded.syncType).toBe(proto.HistorySync.HistorySyncType.RECENT);
+    expect(decoded.chunkOrder).toBe(1);
+    const [conversation] = decoded.conversations!;
+    expect(conversation!.id).toBe("123@s.whatsapp.net");
+    const message = conversation!.messages![0]!.message!;
+    expect(message.key?.id).toBe("MSG1");
+    expect(message.message?.conversation).toBe("from history");
+    expect(message.messageTimestamp).toBe(1_700_000_000n);
+  });
+});
@@ -0,0 +1,178 @@
+import { describe, expect, it } from "bun:test";
+import {
+  buildAck,
+  buildReceipt,
+  decodeNode,
+  encodeNode,
+  parseAck,
+  parseIqResult,
+  parseMessageStanza,
+  parseNotification,
+  parseReceiptStanza,
+  type BinaryNode,
+} from "../dist";
+
+const decode = (node: BinaryNode) => decodeNode(encodeNode(node));
+
+describe("stanza parsers", () => {
+  it("should parse a group message with enc children", () => {
+    const parsed = parseMessageStanza(
+      decode({
+        tag: "message",
+        attrs: {
+          id: "3EB0ABC",
+          from: "120363@g.us",
+          type: "text",
+          participant: "5511999:3@s.whatsapp.net",
+          notify: "Alice",
+          t: "1700000000",
+          offline: "0",
+        },
+        content: [
+          {
+            tag: "enc",
+            attrs: { v: "2", type: "skmsg" },
+            content: new Uint8Array([1, 2, 3]),
+          },
+          {
+            tag: "enc",
+            attrs: { v: "2", type: "pkmsg", count: "1" },
+            content: new Uint8Array([4]),
+          },
+        ],
+      }),
+    );
+
+    expect(parsed.id).toBe("3EB0ABC");
+    expect(parsed.from.server).toBe("g.us");
+    expect(parsed.participant).toMatchObject({
+      jid: "5511999:3@s.whatsapp.net",
+      user: "5511999",
+      device: 3,
+    });
+    expect(parsed.t).toBe(1700000000);
+    expect(parsed.offline).toBe(true);
+    expect(parsed.enc).toHaveLength(2);
+    expect(parsed.enc[0]).toMatchObject({ type: "skmsg", v: 2 });
+    expect(parsed.enc[0]!.ciphertext).toEqual(new Uint8Array([1, 2, 3]));
+    expect(parsed.enc[1]!.count).toBe(1);
+  });
+
+  it("should collect receipt ids from the item list", () => {
+    const parsed = parseReceiptStanza(
+      decode({
+        tag: "receipt",
+        attrs: { id: "a", from: "123@s.whatsapp.net", type: "read" },
+        content: [
+          {
+            tag: "list",
+            attrs: {},
+            content: [
+              { tag: "item", attrs: { id: "b" } },
+              { tag: "item", attrs: { id: "c" } },
+            ],
+          },
+        ],
+      }),
+    );
+    expect(parsed.type).toBe("read");
+    expect(parsed.messageIds).toEqual(["a", "b", "c"]);
+    expect(parsed.offline).toBe(false);
+  });
+
+  it("should parse iq errors, notifications and acks", () => {
+    const iq = parseIqResult(
+      decode({
+        tag: "iq",
+        attrs: { id: "1", type: "error", from: "s.whatsapp.net" },
+        content: [{ tag: "error", attrs: { code: "404", text: "item-not-found" } }],
+      }),
+    );
+    expect(iq.error).toEqual({ code: 404, text: "item-not-found" });
+
+    const notification = parseNotification(
+      decode({
+        tag: "notification",
+        attrs: { id: "2", from: "123@s.whatsapp.net", type: "encrypt", t: "5" },
+      }),
+    );
+    expect(notification.type).toBe("encrypt");
+    expect(notification.t).toBe(5);
+
+    const ack = parseAck(
+      decode({
+        tag: "ack",
+        attrs: { id: "3", class: "message", from: "123@s.whatsapp.net", error: "479" },
+      }),
+    );
+    expect(ack.class).toBe("message");
+    expect(ack.error).toBe(479);
+  });
+
+  it("should reject the wrong tag or missing attributes", () => {
+    const iq = decode({ tag: "iq", attrs: { id: "1", type: "result" } });
+    expect(() => parseMessageStanza(iq)).toThrow("Expected <message> stanza");
+    expect(() =>
+      parseReceiptStanza(decode({ tag: "receipt", attrs: { id: "1" } })),
+    ).toThrow("`from`");
+  });
+});
+
+describe("buildAck / buildReceipt", () => {
+  it("should ack a message like Baileys", () => {
+    const message = decode({
+      tag: "message",
+      attrs: {
+        id: "X",
+        from: "120363@g.us",
+        participant: "123@s.whatsapp.net",
+        type: "text",
+      },
+    });
+    expect(decodeNode(encodeNode(buildAck(message))).attrs).toEqual({
+      id: "X",
+      to: "120363@g.us",
+      class: "message",
+      participant: "123@s.whatsapp.net",
+    });
+
+    const withError = decodeNode(encodeNode(buildAck(message, { errorCode: 500 })));
+    expect(withError.attrs.error).toBe("500");
+    expect(withError.attrs.type).toBe("text");
+  });
+
+  it("should batch receipt ids into a list", () => {
+    const receipt = decodeNode(
+      encodeNode(
+        buildReceipt({
+          jid: "123@s.whatsapp.net",
+          messageIds: ["a", "b"],
+          type: "read",
+          timestamp: 42,
+        }),
+      ),
+    );
+    expect(receipt.attrs).toEqual({
+      id: "a",
+      t: "42",
+      to: "123@s.whatsapp.net",
+      type: "read",
+    });
+    expect(receipt.getChild("list")?.getChildren("item")[0]?.attrs.id).toBe("b");
+  });
+
+  it("should address sender receipts to the participant", () => {
+    const receipt = buildReceipt({
+      jid: "123@s.whatsapp.net",
+      participant: "123:2@s.whatsapp.net",
+      messageIds: ["a"],
+      type: "sender",
+    });
+    expect(decodeNode(receipt.encode()).attrs).toEqual({
+      id: "a",
+      recipient: "123@s.whatsapp.net",
+      to: "123:2@s.whatsapp.net",
+      type: "sender",
+    });
+  });
+});